@@ -0,0 +1,338 @@
+use crate::{ProgramPhase, TimerOutput};
+
+use serde::{Deserialize, Serialize};
+
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+/**
+ * Every Sound the App Ships With, Usable as Cues in a Program
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Sound {
+    ProgramStart,
+    ProgramDone,
+    ProgramStopped,
+    TimerDone,
+    Pause,
+    Resume,
+    Reset,
+    Skip,
+}
+
+impl Sound {
+    pub const ALL: [Sound; 8] = [
+        Sound::ProgramStart,
+        Sound::ProgramDone,
+        Sound::ProgramStopped,
+        Sound::TimerDone,
+        Sound::Pause,
+        Sound::Resume,
+        Sound::Reset,
+        Sound::Skip,
+    ];
+
+    /**
+     * The Sound's MP3, Embedded so Playback Does Not Depend on the Working Directory or Page Path
+     */
+    pub fn bytes(&self) -> &'static [u8] {
+        match self {
+            Sound::ProgramStart => include_bytes!("../assets/Program-Start-Sound.mp3"),
+            Sound::ProgramDone => include_bytes!("../assets/Program-Done-Sound.mp3"),
+            Sound::ProgramStopped => include_bytes!("../assets/Program-Stopped-Sound.mp3"),
+            Sound::TimerDone => include_bytes!("../assets/Timer-Done-Sound.mp3"),
+            Sound::Pause => include_bytes!("../assets/Pause-Sound.mp3"),
+            Sound::Resume => include_bytes!("../assets/Resume-Sound.mp3"),
+            Sound::Reset => include_bytes!("../assets/Reset-Sound.mp3"),
+            Sound::Skip => include_bytes!("../assets/Skip-Sound.mp3"),
+        }
+    }
+
+    /**
+     * The Name Used in Program Files and the Text Format, e.g. `timer_done`
+     */
+    pub fn name(&self) -> &'static str {
+        match self {
+            Sound::ProgramStart => "program_start",
+            Sound::ProgramDone => "program_done",
+            Sound::ProgramStopped => "program_stopped",
+            Sound::TimerDone => "timer_done",
+            Sound::Pause => "pause",
+            Sound::Resume => "resume",
+            Sound::Reset => "reset",
+            Sound::Skip => "skip",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Sound> {
+        Sound::ALL.into_iter().find(|sound| sound.name() == name)
+    }
+}
+
+/**
+ * The Cues Played for One FSM Output, in Order, Whichever Frontend Is Showing It
+ */
+pub fn cues_for(output: &TimerOutput) -> Vec<Sound> {
+    match output {
+        TimerOutput::ProgramStopped { .. } => vec![Sound::ProgramStopped],
+        TimerOutput::TimerProgress { remaining } if remaining.is_zero() => vec![Sound::TimerDone],
+        TimerOutput::TimerPaused => vec![Sound::Pause],
+        TimerOutput::TimerResumed { .. } => vec![Sound::Resume],
+        TimerOutput::TimerReset { .. } => vec![Sound::Reset],
+        TimerOutput::PlaySound { sound } => vec![*sound],
+        TimerOutput::PhaseChange {
+            prev_phase,
+            next_phase,
+            phase_completed,
+        } => {
+            let mut cues = vec![];
+            match prev_phase {
+                ProgramPhase::BeginProgram => cues.push(Sound::ProgramStart),
+                ProgramPhase::TimeFor { .. } if !phase_completed => cues.push(Sound::Skip),
+                ProgramPhase::ReceiveInput { .. } => cues.push(Sound::TimerDone),
+                _ => {}
+            }
+            if let ProgramPhase::BeginProgram | ProgramPhase::EndProgram = next_phase {
+                cues.push(Sound::ProgramDone);
+            }
+            cues
+        }
+        TimerOutput::NoChange
+        | TimerOutput::TimerProgress { .. }
+        | TimerOutput::DisplayText { .. } => vec![],
+    }
+}
+
+/**
+ * Why a Sound Did Not Play
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub enum AudioError {
+    NoOutputDevice(String),
+    Decode(String),
+    // The Browser Refuses to Play Until the User Interacts With the Page
+    Blocked,
+    Playback(String),
+}
+
+impl fmt::Display for AudioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AudioError::NoOutputDevice(reason) => write!(f, "no audio output device: {}", reason),
+            AudioError::Decode(reason) => write!(f, "could not decode sound: {}", reason),
+            AudioError::Blocked => write!(f, "sound blocked by the browser"),
+            AudioError::Playback(reason) => write!(f, "could not play sound: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for AudioError {}
+
+pub type AudioErrorHandler = Rc<dyn Fn(AudioError)>;
+
+/**
+ * Something That Can Play the App's Sounds, Picked per Target by `default_backend`
+ */
+pub trait AudioBackend {
+    fn play(&self, sound: Sound) -> Result<(), AudioError>;
+
+    /**
+     * Receives Errors Found After `play` Returned, Like the Browser Blocking Autoplay
+     */
+    fn set_error_handler(&self, _handler: AudioErrorHandler) {}
+
+    /**
+     * Blocks Until Every Sound Already Played Has Finished, for Frontends About to Exit
+     */
+    fn drain(&self) {}
+}
+
+/**
+ * Plays Sounds Through the Browser From Object URLs, Created Once per Sound
+ */
+#[cfg(target_arch = "wasm32")]
+#[derive(Default)]
+pub struct WebAudio {
+    urls: RefCell<std::collections::HashMap<Sound, String>>,
+    error_handler: RefCell<Option<AudioErrorHandler>>,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl WebAudio {
+    fn url(&self, sound: Sound) -> Result<String, AudioError> {
+        if let Some(url) = self.urls.borrow().get(&sound) {
+            return Ok(url.clone());
+        }
+        let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(sound.bytes()));
+        let mut options = web_sys::BlobPropertyBag::new();
+        options.type_("audio/mpeg");
+        let blob = web_sys::Blob::new_with_u8_array_sequence_and_options(&parts, &options)
+            .map_err(|err| AudioError::Playback(format!("could not create blob: {:?}", err)))?;
+        let url = web_sys::Url::create_object_url_with_blob(&blob).map_err(|err| {
+            AudioError::Playback(format!("could not create object url: {:?}", err))
+        })?;
+        self.urls.borrow_mut().insert(sound, url.clone());
+        Ok(url)
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl AudioBackend for WebAudio {
+    fn play(&self, sound: Sound) -> Result<(), AudioError> {
+        use wasm_bindgen::JsCast;
+
+        let audio = web_sys::HtmlAudioElement::new_with_src(&self.url(sound)?).map_err(|err| {
+            AudioError::Playback(format!("could not create audio element: {:?}", err))
+        })?;
+        let promise = audio
+            .play()
+            .map_err(|err| AudioError::Playback(format!("{:?}", err)))?;
+
+        // Autoplay Rejections Only Arrive Once the Promise Settles
+        let error_handler = self.error_handler.borrow().clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            if let Err(err) = wasm_bindgen_futures::JsFuture::from(promise).await {
+                let error = match err.dyn_ref::<web_sys::DomException>() {
+                    Some(exception) if exception.name() == "NotAllowedError" => AudioError::Blocked,
+                    _ => AudioError::Playback(format!("{:?}", err)),
+                };
+                if let Some(error_handler) = error_handler {
+                    error_handler(error);
+                }
+            }
+        });
+        Ok(())
+    }
+
+    fn set_error_handler(&self, handler: AudioErrorHandler) {
+        *self.error_handler.borrow_mut() = Some(handler);
+    }
+}
+
+/**
+ * Plays Sounds on the Default Output Device, Opened on First Use
+ */
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Default)]
+pub struct RodioAudio {
+    output: RefCell<Option<(rodio::OutputStream, rodio::OutputStreamHandle)>>,
+    /// One Sink per Sound Still Playing, so Cues Overlap and Can Be Waited On
+    sinks: RefCell<Vec<rodio::Sink>>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl AudioBackend for RodioAudio {
+    fn play(&self, sound: Sound) -> Result<(), AudioError> {
+        let mut output = self.output.borrow_mut();
+        if output.is_none() {
+            let stream = rodio::OutputStream::try_default()
+                .map_err(|err| AudioError::NoOutputDevice(err.to_string()))?;
+            *output = Some(stream);
+        }
+        // The Stream Has to Outlive the Sound, so It Stays Here Once Opened
+        let (_, handle) = output.as_ref().unwrap();
+        let source = rodio::Decoder::new(std::io::Cursor::new(sound.bytes()))
+            .map_err(|err| AudioError::Decode(err.to_string()))?;
+        let sink =
+            rodio::Sink::try_new(handle).map_err(|err| AudioError::Playback(err.to_string()))?;
+        sink.append(source);
+        let mut sinks = self.sinks.borrow_mut();
+        sinks.retain(|sink| !sink.empty());
+        sinks.push(sink);
+        Ok(())
+    }
+
+    fn drain(&self) {
+        for sink in self.sinks.borrow_mut().drain(..) {
+            sink.sleep_until_end();
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn default_backend() -> Box<dyn AudioBackend> {
+    Box::new(WebAudio::default())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn default_backend() -> Box<dyn AudioBackend> {
+    Box::new(RodioAudio::default())
+}
+
+#[cfg(test)]
+mod audio_util_tests {
+
+    use super::{cues_for, Sound};
+    use crate::ProgramPhase::*;
+    use crate::TimerOutput::*;
+    use std::time::Duration;
+
+    #[test]
+    fn names_round_trip() {
+        for sound in Sound::ALL {
+            assert_eq!(Some(sound), Sound::from_name(sound.name()));
+        }
+        assert_eq!(None, Sound::from_name("trumpet"));
+    }
+
+    #[test]
+    fn names_match_serde() {
+        for sound in Sound::ALL {
+            assert_eq!(
+                format!("\"{}\"", sound.name()),
+                serde_json::to_string(&sound).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn assets_embedded() {
+        for sound in Sound::ALL {
+            assert!(!sound.bytes().is_empty());
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn assets_decode() {
+        for sound in Sound::ALL {
+            assert!(rodio::Decoder::new(std::io::Cursor::new(sound.bytes())).is_ok());
+        }
+    }
+
+    #[test]
+    fn cues_for_outputs() {
+        assert_eq!(
+            vec![Sound::TimerDone],
+            cues_for(&TimerProgress {
+                remaining: Duration::ZERO
+            })
+        );
+        assert!(cues_for(&TimerProgress {
+            remaining: Duration::from_secs(1)
+        })
+        .is_empty());
+        assert_eq!(
+            vec![Sound::Skip],
+            cues_for(&PhaseChange {
+                prev_phase: TimeFor {
+                    duration: 5,
+                    label: None,
+                },
+                next_phase: ReceiveInput { label: None },
+                phase_completed: false,
+            })
+        );
+        // A One Phase Program Starts and Ends in the Same Change
+        assert_eq!(
+            vec![Sound::ProgramStart, Sound::ProgramDone],
+            cues_for(&PhaseChange {
+                prev_phase: BeginProgram,
+                next_phase: EndProgram,
+                phase_completed: true,
+            })
+        );
+    }
+}
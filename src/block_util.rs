@@ -0,0 +1,225 @@
+use crate::ProgramPhase;
+
+use std::fmt;
+
+/**
+ * A Program Written as Nested Blocks, so Loops Need No Jump Targets or Hand-Kept Counters
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProgramBlock {
+    Phase(ProgramPhase),
+    Loop { count: i8, body: Vec<ProgramBlock> },
+}
+
+impl From<ProgramPhase> for ProgramBlock {
+    fn from(phase: ProgramPhase) -> ProgramBlock {
+        ProgramBlock::Phase(phase)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlockError {
+    InvalidLoopCount(i8),
+    EmptyLoop,
+    ControlPhase(ProgramPhase),
+}
+
+impl fmt::Display for BlockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlockError::InvalidLoopCount(count) => write!(
+                f,
+                "loop count must be between 1 and {}, found {}",
+                i8::MAX,
+                count
+            ),
+            BlockError::EmptyLoop => write!(f, "loop body must contain at least one phase"),
+            BlockError::ControlPhase(phase) => write!(
+                f,
+                "{:?} is generated by loops and cannot be used as a block",
+                phase
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BlockError {}
+
+struct Compiler {
+    phases: Vec<ProgramPhase>,
+    variables: Vec<i8>,
+}
+
+impl Compiler {
+    fn blocks(&mut self, blocks: &[ProgramBlock]) -> Result<(), BlockError> {
+        for block in blocks {
+            self.block(block)?;
+        }
+        Ok(())
+    }
+
+    /**
+     * A Loop Compiles to a Counter Reset, the Body, a Counter Decrement and a Jump Back to the Body
+     */
+    fn block(&mut self, block: &ProgramBlock) -> Result<(), BlockError> {
+        match block {
+            ProgramBlock::Phase(
+                phase @ (ProgramPhase::Repeat { .. }
+                | ProgramPhase::OffsetVariable { .. }
+                | ProgramPhase::SetVariable { .. }),
+            ) => return Err(BlockError::ControlPhase(phase.clone())),
+            ProgramBlock::Phase(phase) => self.phases.push(phase.clone()),
+            ProgramBlock::Loop { count, .. } if *count < 1 => {
+                return Err(BlockError::InvalidLoopCount(*count))
+            }
+            ProgramBlock::Loop { body, .. } if body.is_empty() => {
+                return Err(BlockError::EmptyLoop)
+            }
+            ProgramBlock::Loop { count, body } => {
+                let var_index = self.variables.len();
+                self.variables.push(*count);
+                self.phases.push(ProgramPhase::SetVariable {
+                    var_index,
+                    value: *count,
+                });
+                let body_start = self.phases.len();
+                self.blocks(body)?;
+
+                self.phases.push(ProgramPhase::OffsetVariable {
+                    var_index,
+                    offset: -1,
+                });
+                self.phases.push(ProgramPhase::Repeat {
+                    to_phase: body_start,
+                    var_index,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/**
+ * Flattens Blocks Into the Phases and Initial Variables the FSM Runs, One Counter per Loop
+ */
+pub fn compile(blocks: &[ProgramBlock]) -> Result<(Vec<ProgramPhase>, Vec<i8>), BlockError> {
+    let mut compiler = Compiler {
+        phases: vec![],
+        variables: vec![],
+    };
+    compiler.blocks(blocks)?;
+    Ok((compiler.phases, compiler.variables))
+}
+
+#[cfg(test)]
+mod block_util_tests {
+
+    use super::{compile, BlockError, ProgramBlock};
+    use crate::validator_util::check;
+    use crate::ProgramPhase::*;
+
+    #[test]
+    fn nested_loops() {
+        let blocks = vec![
+            ReceiveInput { label: None }.into(),
+            ProgramBlock::Loop {
+                count: 3,
+                body: vec![
+                    ProgramBlock::Loop {
+                        count: 2,
+                        body: vec![TimeFor {
+                            duration: 5,
+                            label: None,
+                        }
+                        .into()],
+                    },
+                    ReceiveInput { label: None }.into(),
+                ],
+            },
+        ];
+        let (phases, variables) = compile(&blocks).unwrap();
+        assert_eq!(
+            vec![
+                ReceiveInput { label: None },
+                SetVariable {
+                    var_index: 0,
+                    value: 3,
+                },
+                SetVariable {
+                    var_index: 1,
+                    value: 2,
+                },
+                TimeFor {
+                    duration: 5,
+                    label: None,
+                },
+                OffsetVariable {
+                    var_index: 1,
+                    offset: -1,
+                },
+                Repeat {
+                    to_phase: 3,
+                    var_index: 1,
+                },
+                ReceiveInput { label: None },
+                OffsetVariable {
+                    var_index: 0,
+                    offset: -1,
+                },
+                Repeat {
+                    to_phase: 2,
+                    var_index: 0,
+                },
+            ],
+            phases
+        );
+        assert_eq!(vec![3, 2], variables);
+        assert!(check(&phases, &variables).is_ok());
+    }
+
+    #[test]
+    fn inserting_keeps_targets() {
+        let mut blocks = vec![ProgramBlock::Loop {
+            count: 2,
+            body: vec![ReceiveInput { label: None }.into()],
+        }];
+        blocks.insert(0, EndProgram.into());
+        let (phases, _) = compile(&blocks).unwrap();
+        assert_eq!(
+            Repeat {
+                to_phase: 2,
+                var_index: 0,
+            },
+            phases[4]
+        );
+    }
+
+    #[test]
+    fn invalid_blocks() {
+        assert_eq!(
+            Err(BlockError::InvalidLoopCount(0)),
+            compile(&[ProgramBlock::Loop {
+                count: 0,
+                body: vec![ReceiveInput { label: None }.into()],
+            }])
+        );
+        assert_eq!(
+            Err(BlockError::EmptyLoop),
+            compile(&[ProgramBlock::Loop {
+                count: 1,
+                body: vec![],
+            }])
+        );
+        assert_eq!(
+            Err(BlockError::ControlPhase(Repeat {
+                to_phase: 0,
+                var_index: 0,
+            })),
+            compile(&[Repeat {
+                to_phase: 0,
+                var_index: 0,
+            }
+            .into()])
+        );
+    }
+}
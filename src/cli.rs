@@ -0,0 +1,209 @@
+//! Runs a Program in the Terminal Without the Slint Window, e.g. Over SSH or in tmux
+
+use oxidoro_timer::audio_util::{cues_for, default_backend, AudioBackend};
+use oxidoro_timer::cli_util::{output_line, status_line};
+use oxidoro_timer::clock_util::{Clock, SystemClock, TickScheduler, TICK_INTERVAL};
+use oxidoro_timer::history_util::{self, HistoryRecorder};
+use oxidoro_timer::keymap_util::{load_keymap, Keymap};
+use oxidoro_timer::program_util::Program;
+use oxidoro_timer::template_util;
+use oxidoro_timer::timer_util::TimerFSM;
+use oxidoro_timer::{TimerInput, TimerStatus};
+
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::{cursor, terminal, QueueableCommand};
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::ExitCode;
+
+const USAGE: &str = "usage: oxidoro-cli <program.json | program.toml | program.txt | template>";
+
+/**
+ * A Program File, Picking the Format From the Extension, or a Built-In Template by Name
+ */
+fn load_program(source: &str) -> Result<Program, String> {
+    let path = Path::new(source);
+    if !path.exists() {
+        return template_util::built_in()
+            .into_iter()
+            .find(|program| program.name.eq_ignore_ascii_case(source))
+            .ok_or_else(|| format!("'{}' is neither a file nor a template", source));
+    }
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("json" | "toml") => Program::load(path).map_err(|error| error.to_string()),
+        _ => {
+            let text = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+            let name = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .unwrap_or(source);
+            Program::from_text(name, &text).map_err(|error| error.to_string())
+        }
+    }
+}
+
+/**
+ * The Key as the Text Slint Would Report, so the GUI's Keymap Applies Unchanged
+ */
+fn key_text(code: KeyCode) -> Option<String> {
+    match code {
+        KeyCode::Char(character) => Some(character.to_string()),
+        KeyCode::Enter => Some("\n".to_string()),
+        KeyCode::Esc => Some("\u{1b}".to_string()),
+        KeyCode::Tab => Some("\t".to_string()),
+        KeyCode::Backspace => Some("\u{8}".to_string()),
+        _ => None,
+    }
+}
+
+/**
+ * Prints a Line Above the Live Status Line; Raw Mode Needs the Explicit Carriage Return
+ */
+fn print_line(out: &mut impl Write, line: &str) -> io::Result<()> {
+    out.queue(cursor::MoveToColumn(0))?
+        .queue(terminal::Clear(terminal::ClearType::CurrentLine))?;
+    write!(out, "{}\r\n", line)
+}
+
+struct Runner {
+    model: TimerFSM,
+    scheduler: TickScheduler,
+    history: HistoryRecorder,
+    audio: Box<dyn AudioBackend>,
+    audio_failed: bool,
+}
+
+impl Runner {
+    /**
+     * Feeds One Input to the FSM and Handles Its Output and Queued Effects Like the GUI Does
+     */
+    fn input(&mut self, input: TimerInput, out: &mut impl Write) -> io::Result<()> {
+        let mut outputs = vec![self.model.input(input)];
+        outputs.append(&mut self.model.take_effects());
+        for output in outputs {
+            self.scheduler.observe(&output, SystemClock.now());
+            if let Err(error) = self.history.record(&output) {
+                print_line(out, &format!("history: {}", error))?;
+            }
+            // One Warning Is Enough When There Is No Sound Device, e.g. Over SSH
+            for sound in cues_for(&output) {
+                if self.audio_failed {
+                    break;
+                }
+                if let Err(error) = self.audio.play(sound) {
+                    self.audio_failed = true;
+                    print_line(out, &format!("audio: {}", error))?;
+                }
+            }
+            if let Some(line) = output_line(&output) {
+                print_line(out, &line)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn run(&mut self, keymap: &Keymap, out: &mut impl Write) -> io::Result<()> {
+        self.input(TimerInput::Start, out)?;
+        loop {
+            if self.scheduler.due(SystemClock.now()) {
+                self.input(TimerInput::Step, out)?;
+            }
+            if event::poll(TICK_INTERVAL)? {
+                if let Event::Key(key) = event::read()? {
+                    if key.kind != KeyEventKind::Press {
+                        continue;
+                    }
+                    let ctrl_c = key.modifiers.contains(KeyModifiers::CONTROL)
+                        && key.code == KeyCode::Char('c');
+                    if ctrl_c || key.code == KeyCode::Char('q') {
+                        // Quitting Mid-Run Counts as Stopping It, Like Switching Programs in the GUI
+                        if let Err(error) = self.history.close() {
+                            print_line(out, &format!("history: {}", error))?;
+                        }
+                        self.audio.drain();
+                        return Ok(());
+                    }
+                    let input = key_text(key.code)
+                        .and_then(|text| keymap.input_for(&text, self.model.status()));
+                    if let Some(input) = input {
+                        self.input(input, out)?;
+                    }
+                }
+            }
+            // Back to Idle Means the Program Reached Its End or Was Stopped, so There Is Nothing Left to Run
+            if self.model.status() == TimerStatus::Idle {
+                // The Done or Stopped Cue Was Just Queued, and Exiting Closes the Output Stream
+                self.audio.drain();
+                return Ok(());
+            }
+            out.queue(cursor::MoveToColumn(0))?
+                .queue(terminal::Clear(terminal::ClearType::CurrentLine))?;
+            write!(out, "{}", status_line(&self.model.snapshot()))?;
+            out.flush()?;
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let Some(source) = std::env::args().nth(1) else {
+        eprintln!("{}", USAGE);
+        return ExitCode::from(2);
+    };
+    let program = match load_program(&source) {
+        Ok(program) => program,
+        Err(error) => {
+            eprintln!("{}", error);
+            return ExitCode::FAILURE;
+        }
+    };
+    let model = match program.to_fsm() {
+        Ok(model) => model,
+        Err(error) => {
+            eprintln!("{}: {}", program.name, error);
+            return ExitCode::FAILURE;
+        }
+    };
+    let keymap = load_keymap().unwrap_or_else(|error| {
+        eprintln!("keymap: {}, using the defaults", error);
+        Keymap::default()
+    });
+    // Quitting Is Checked Before the Keymap, so Say So Rather Than Ignore the Binding Silently
+    if let Some(action) = keymap.action("q") {
+        eprintln!(
+            "keymap: q quits here, so its {:?} binding only works in the window",
+            action
+        );
+    }
+    let mut history = HistoryRecorder::new(history_util::default_store(), &program.name);
+    if let Err(error) = history.load() {
+        eprintln!("history: {}", error);
+    }
+
+    let mut runner = Runner {
+        model,
+        scheduler: TickScheduler::new(TICK_INTERVAL),
+        history,
+        audio: default_backend(),
+        audio_failed: false,
+    };
+    if keymap == Keymap::default() {
+        println!(
+            "{}: space pauses, enter answers input, n skips, r resets, q quits",
+            program.name
+        );
+    } else {
+        println!("{}: q quits, other keys follow keys.toml", program.name);
+    }
+    let mut out = io::stdout();
+    let result = terminal::enable_raw_mode().and_then(|()| runner.run(&keymap, &mut out));
+    // Leave the Terminal Usable Even if the Loop Failed
+    let _ = terminal::disable_raw_mode();
+    println!();
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
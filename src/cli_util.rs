@@ -0,0 +1,149 @@
+use crate::duration_util::{display_seconds, program_time_string, seconds_to_h_m_s_display_string};
+use crate::timer_util::TimerSnapshot;
+use crate::{ProgramPhase, TimerOutput, TimerStatus};
+
+/**
+ * A Line for the Terminal's Scrollback When an Output Is Worth Keeping, Like a Phase Change
+ *
+ * Countdown progress is left to `status_line`, which is redrawn in place.
+ */
+pub fn output_line(output: &TimerOutput) -> Option<String> {
+    match output {
+        TimerOutput::ProgramStopped { .. } => Some("Stopped".to_string()),
+        TimerOutput::TimerPaused => Some("Paused".to_string()),
+        TimerOutput::TimerResumed { .. } => Some("Resumed".to_string()),
+        TimerOutput::TimerReset { .. } => Some("Reset".to_string()),
+        TimerOutput::DisplayText { text } => Some(format!("> {}", text)),
+        TimerOutput::PhaseChange {
+            prev_phase,
+            next_phase,
+            phase_completed,
+        } => {
+            let skipped = match prev_phase {
+                ProgramPhase::TimeFor { .. } if !phase_completed => " (skipped)",
+                _ => "",
+            };
+            let next = match next_phase {
+                ProgramPhase::TimeFor { duration, label } => format!(
+                    "Time {}{}",
+                    seconds_to_h_m_s_display_string(*duration),
+                    label
+                        .as_ref()
+                        .map(|label| format!(" {}", label))
+                        .unwrap_or_default()
+                ),
+                ProgramPhase::ReceiveInput { label } => format!(
+                    "Waiting for input{}",
+                    label
+                        .as_ref()
+                        .map(|label| format!(": {}", label))
+                        .unwrap_or_default()
+                ),
+                ProgramPhase::EndProgram => "Program done".to_string(),
+                _ => return None,
+            };
+            Some(format!("{}{}", next, skipped))
+        }
+        TimerOutput::NoChange
+        | TimerOutput::TimerProgress { .. }
+        | TimerOutput::PlaySound { .. } => None,
+    }
+}
+
+/**
+ * The Live Line Under the Scrollback: Status, the Current Timer, Its Label and the Program Time
+ */
+pub fn status_line(snapshot: &TimerSnapshot) -> String {
+    let time = match (snapshot.status, snapshot.remaining) {
+        (TimerStatus::Idle, _) => "Ready".to_string(),
+        (TimerStatus::AwaitingInput, _) => "Input".to_string(),
+        (_, Some(remaining)) => seconds_to_h_m_s_display_string(display_seconds(remaining)),
+        (_, None) => String::new(),
+    };
+    let label = snapshot
+        .phase
+        .as_ref()
+        .and_then(|phase| phase.label())
+        .unwrap_or_default();
+    [
+        format!("[{:?}]", snapshot.status),
+        time,
+        label.to_string(),
+        program_time_string(snapshot),
+    ]
+    .into_iter()
+    .filter(|part| !part.is_empty())
+    .collect::<Vec<String>>()
+    .join("  ")
+}
+
+#[cfg(test)]
+mod cli_util_tests {
+
+    use super::{output_line, status_line};
+    use crate::timer_util::TimerSnapshot;
+    use crate::ProgramPhase::*;
+    use crate::TimerOutput::*;
+    use crate::TimerStatus;
+    use std::time::Duration;
+
+    #[test]
+    fn phase_change_lines() {
+        assert_eq!(
+            Some("Time 00:25:00 Focus".to_string()),
+            output_line(&PhaseChange {
+                prev_phase: BeginProgram,
+                next_phase: TimeFor {
+                    duration: 1500,
+                    label: Some("Focus".into()),
+                },
+                phase_completed: true,
+            })
+        );
+        assert_eq!(
+            Some("Waiting for input: Ready? (skipped)".to_string()),
+            output_line(&PhaseChange {
+                prev_phase: TimeFor {
+                    duration: 5,
+                    label: None,
+                },
+                next_phase: ReceiveInput {
+                    label: Some("Ready?".into()),
+                },
+                phase_completed: false,
+            })
+        );
+        assert_eq!(
+            None,
+            output_line(&TimerProgress {
+                remaining: Duration::from_secs(3)
+            })
+        );
+    }
+
+    #[test]
+    fn live_status() {
+        let mut snapshot = TimerSnapshot {
+            status: TimerStatus::Running,
+            phase_index: 0,
+            phase: Some(TimeFor {
+                duration: 60,
+                label: Some("Focus".into()),
+            }),
+            remaining: Some(Duration::from_millis(41_200)),
+            elapsed: None,
+            program_remaining: Some(Duration::from_secs(100)),
+            program_total: Some(Duration::from_secs(160)),
+            variables: vec![],
+            program_len: 1,
+        };
+        assert_eq!(
+            "[Running]  00:00:42  Focus  Program: 00:01:40 left",
+            status_line(&snapshot)
+        );
+        snapshot.status = TimerStatus::Idle;
+        snapshot.phase = None;
+        snapshot.program_remaining = None;
+        assert_eq!("[Idle]  Ready", status_line(&snapshot));
+    }
+}
@@ -0,0 +1,287 @@
+use crate::ProgramPhase;
+use crate::TimerOutput;
+
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+/**
+ * How Often the Display Is Refreshed While a Timer Runs; the FSM Measures Elapsed Time Itself
+ */
+pub const TICK_INTERVAL: Duration = Duration::from_millis(100);
+
+/**
+ * A Monotonic Source of Time, Measured From an Arbitrary Starting Point
+ */
+pub trait Clock: Send {
+    fn now(&self) -> Duration;
+}
+
+/**
+ * The Real Monotonic Clock, Which Also Works in the Browser
+ */
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        static START: OnceLock<web_time::Instant> = OnceLock::new();
+        START.get_or_init(web_time::Instant::now).elapsed()
+    }
+}
+
+/**
+ * Virtual Time That Only Moves When Told To; Clones Share the Same Time
+ */
+#[derive(Debug, Clone, Default)]
+pub struct ManualClock {
+    now: Arc<Mutex<Duration>>,
+}
+
+impl ManualClock {
+    pub fn new() -> ManualClock {
+        ManualClock::default()
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.now.lock().unwrap() += by;
+    }
+
+    pub fn set(&self, to: Duration) {
+        *self.now.lock().unwrap() = to;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        *self.now.lock().unwrap()
+    }
+}
+
+/**
+ * Decides When the Host Should Feed TimerInput::Step to the FSM
+ *
+ * The host wakes up whenever it can (a platform timer, a terminal loop) and asks if a tick
+ * is due. Ticks missed while the host was throttled or suspended collapse into a single Step,
+ * which is enough since the FSM catches up on the real elapsed time.
+ */
+#[derive(Debug, Clone)]
+pub struct TickScheduler {
+    interval: Duration,
+    next_tick: Option<Duration>,
+}
+
+impl TickScheduler {
+    pub fn new(interval: Duration) -> TickScheduler {
+        TickScheduler {
+            interval,
+            next_tick: None,
+        }
+    }
+
+    pub fn start(&mut self, now: Duration) {
+        self.next_tick = Some(now + self.interval);
+    }
+
+    pub fn stop(&mut self) {
+        self.next_tick = None;
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.next_tick.is_some()
+    }
+
+    /**
+     * Starts Ticking When a Timer Begins Counting Down and Stops Once Nothing Is Counting
+     */
+    pub fn observe(&mut self, output: &TimerOutput, now: Duration) {
+        match output {
+            TimerOutput::PhaseChange {
+                next_phase: ProgramPhase::TimeFor { .. },
+                ..
+            }
+            | TimerOutput::TimerResumed { .. } => self.start(now),
+            TimerOutput::PhaseChange { .. }
+            | TimerOutput::ProgramStopped { .. }
+            | TimerOutput::TimerPaused => self.stop(),
+            _ => {}
+        }
+    }
+
+    /**
+     * Whether a Step Is Due at `now`, Scheduling the Next Tick From `now` If So
+     */
+    pub fn due(&mut self, now: Duration) -> bool {
+        match self.next_tick {
+            Some(next_tick) if now >= next_tick => {
+                self.next_tick = Some(now + self.interval);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod clock_util_tests {
+
+    use super::{Clock, ManualClock, TickScheduler, TICK_INTERVAL};
+    use crate::ProgramPhase::*;
+    use crate::TimerFSM;
+    use crate::TimerInput;
+    use crate::TimerOutput::{self, *};
+    use std::time::Duration;
+
+    struct Pipeline {
+        clock: ManualClock,
+        model: TimerFSM,
+        scheduler: TickScheduler,
+        outputs: Vec<TimerOutput>,
+    }
+
+    impl Pipeline {
+        /**
+         * A Single Three Second Timer Reading the Pipeline's Virtual Clock
+         */
+        fn new() -> Pipeline {
+            let clock = ManualClock::new();
+            Pipeline {
+                model: TimerFSM::with_clock(
+                    vec![TimeFor {
+                        duration: 3,
+                        label: None,
+                    }],
+                    None,
+                    Box::new(clock.clone()),
+                )
+                .unwrap(),
+                clock,
+                scheduler: TickScheduler::new(TICK_INTERVAL),
+                outputs: vec![],
+            }
+        }
+
+        fn input(&mut self, input: TimerInput) {
+            let output = self.model.input(input);
+            self.scheduler.observe(&output, self.clock.now());
+            self.outputs.push(output);
+        }
+
+        /**
+         * Moves Virtual Time Forward, Waking the Host at Each of the Given Gaps
+         */
+        fn wake_after(&mut self, gaps: impl IntoIterator<Item = Duration>) {
+            for gap in gaps {
+                self.clock.advance(gap);
+                if self.scheduler.due(self.clock.now()) {
+                    self.input(TimerInput::Step);
+                }
+            }
+        }
+
+        fn finished(&self) -> bool {
+            self.outputs.contains(&PhaseChange {
+                prev_phase: TimeFor {
+                    duration: 3,
+                    label: None,
+                },
+                next_phase: EndProgram,
+                phase_completed: true,
+            })
+        }
+    }
+
+    #[test]
+    fn manual_clock_is_shared() {
+        let clock = ManualClock::new();
+        let handle = clock.clone();
+        handle.advance(Duration::from_secs(2));
+        assert_eq!(Duration::from_secs(2), clock.now());
+        handle.set(Duration::from_millis(5));
+        assert_eq!(Duration::from_millis(5), clock.now());
+    }
+
+    #[test]
+    fn scheduler_follows_outputs() {
+        let mut scheduler = TickScheduler::new(TICK_INTERVAL);
+        let now = Duration::from_secs(1);
+        scheduler.observe(
+            &PhaseChange {
+                prev_phase: BeginProgram,
+                next_phase: TimeFor {
+                    duration: 3,
+                    label: None,
+                },
+                phase_completed: true,
+            },
+            now,
+        );
+        assert!(scheduler.is_running());
+        assert!(!scheduler.due(now + Duration::from_millis(50)));
+        assert!(scheduler.due(now + TICK_INTERVAL));
+        scheduler.observe(&TimerPaused, now);
+        assert!(!scheduler.is_running());
+        assert!(!scheduler.due(now + Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn steady_ticks_finish_on_time() {
+        let mut pipeline = Pipeline::new();
+        pipeline.input(TimerInput::Start);
+        pipeline.wake_after(vec![TICK_INTERVAL; 30]);
+        assert!(!pipeline.finished());
+        pipeline.wake_after(vec![TICK_INTERVAL; 2]);
+        assert!(pipeline.finished());
+        assert!(!pipeline.scheduler.is_running());
+    }
+
+    #[test]
+    fn jittery_host_does_not_drift() {
+        let mut pipeline = Pipeline::new();
+        pipeline.input(TimerInput::Start);
+        let gaps: Vec<Duration> = [130, 90, 370, 1000, 60, 240, 1010]
+            .iter()
+            .map(|&millis| Duration::from_millis(millis))
+            .collect();
+        pipeline.wake_after(gaps);
+        assert_eq!(
+            Some(&TimerProgress {
+                remaining: Duration::from_millis(100)
+            }),
+            pipeline.outputs.last()
+        );
+    }
+
+    #[test]
+    fn long_pause_keeps_remaining_time() {
+        let mut pipeline = Pipeline::new();
+        pipeline.input(TimerInput::Start);
+        pipeline.wake_after(vec![TICK_INTERVAL; 15]);
+        pipeline.input(TimerInput::Pause);
+        pipeline.wake_after([Duration::from_secs(3600)]);
+        pipeline.input(TimerInput::Resume);
+        assert_eq!(
+            Some(&TimerResumed {
+                remaining: Duration::from_millis(1500)
+            }),
+            pipeline.outputs.last()
+        );
+        pipeline.wake_after(vec![TICK_INTERVAL; 16]);
+        assert!(pipeline.finished());
+    }
+
+    #[test]
+    fn host_suspend_catches_up_in_one_step() {
+        let mut pipeline = Pipeline::new();
+        pipeline.input(TimerInput::Start);
+        pipeline.wake_after([TICK_INTERVAL, Duration::from_secs(600)]);
+        assert_eq!(3, pipeline.outputs.len());
+        assert_eq!(
+            TimerProgress {
+                remaining: Duration::ZERO
+            },
+            pipeline.outputs[2]
+        );
+        pipeline.wake_after([TICK_INTERVAL]);
+        assert!(pipeline.finished());
+    }
+}
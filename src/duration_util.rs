@@ -0,0 +1,262 @@
+use crate::timer_util::TimerSnapshot;
+use crate::{ProgramPhase, TimerStatus};
+
+use std::time::Duration;
+
+/**
+ * Phases Walked Before an Estimate Gives Up, So Deeply Nested Loops Do Not Stall the UI
+ */
+const MAX_STEPS: usize = 1_000_000;
+
+/**
+ * Total Timed Length of a Program Run From the Start, Unrolling Its Loops
+ *
+ * Input phases take as long as the user does, so they count as zero. None if the
+ * program runs too many phases to estimate.
+ */
+pub fn total_duration(program: &[ProgramPhase], variables: &[i8]) -> Option<Duration> {
+    timed_from(program, 0, variables)
+}
+
+/**
+ * Timed Length of the Phases From `start` to the End, Given the Loop Counters at That Point
+ */
+pub fn timed_from(program: &[ProgramPhase], start: usize, variables: &[i8]) -> Option<Duration> {
+    let mut variables = variables.to_vec();
+    let mut phase = start;
+    let mut total = Duration::ZERO;
+    for _ in 0..MAX_STEPS {
+        match program.get(phase) {
+            None | Some(ProgramPhase::BeginProgram | ProgramPhase::EndProgram) => {
+                return Some(total)
+            }
+            Some(ProgramPhase::TimeFor { duration, .. }) => {
+                total += Duration::from_secs(*duration as u64);
+                phase += 1;
+            }
+            Some(ProgramPhase::Repeat {
+                to_phase,
+                var_index,
+            }) => {
+                phase = if variables.get(*var_index).copied().unwrap_or(0) == 0 {
+                    phase + 1
+                } else {
+                    *to_phase
+                };
+            }
+            Some(ProgramPhase::OffsetVariable { var_index, offset }) => {
+                if let Some(variable) = variables.get_mut(*var_index) {
+                    *variable = variable.saturating_add(*offset);
+                }
+                phase += 1;
+            }
+            Some(ProgramPhase::SetVariable { var_index, value }) => {
+                if let Some(variable) = variables.get_mut(*var_index) {
+                    *variable = *value;
+                }
+                phase += 1;
+            }
+            Some(
+                ProgramPhase::ReceiveInput { .. }
+                | ProgramPhase::PlaySound { .. }
+                | ProgramPhase::DisplayText { .. },
+            ) => phase += 1,
+        }
+    }
+    None
+}
+
+/**
+ * Rounds Up, so a Countdown Shows 00:00:01 Until the Very End of the Last Second
+ */
+pub fn display_seconds(remaining: Duration) -> usize {
+    remaining.as_millis().div_ceil(1000) as usize
+}
+
+pub fn seconds_to_h_m_s_display_string(total_seconds: usize) -> String {
+    let hours = total_seconds / 3600;
+    let minutes = (total_seconds - hours * 3600) / 60;
+    let seconds = total_seconds - hours * 3600 - minutes * 60;
+    format!("{:02}:{:02}:{:02}", hours, minutes, seconds)
+}
+
+/**
+ * The Whole Program's Length While Idle, What Is Left of It Otherwise
+ */
+pub fn program_time_string(snapshot: &TimerSnapshot) -> String {
+    match (snapshot.status, snapshot.program_remaining) {
+        (_, None) => String::new(),
+        (TimerStatus::Idle, Some(total)) => format!(
+            "Program: {}",
+            seconds_to_h_m_s_display_string(display_seconds(total))
+        ),
+        (_, Some(remaining)) => format!(
+            "Program: {} left",
+            seconds_to_h_m_s_display_string(display_seconds(remaining))
+        ),
+    }
+}
+
+#[cfg(test)]
+mod duration_util_tests {
+
+    use super::{
+        display_seconds, program_time_string, seconds_to_h_m_s_display_string, timed_from,
+        total_duration,
+    };
+    use crate::block_util::{compile, ProgramBlock};
+    use crate::timer_util::TimerSnapshot;
+    use crate::validator_util::check;
+    use crate::ProgramPhase::*;
+    use crate::TimerStatus;
+    use std::time::Duration;
+
+    #[test]
+    fn straight_line() {
+        assert_eq!(
+            Some(Duration::from_secs(90)),
+            total_duration(
+                &[
+                    TimeFor {
+                        duration: 60,
+                        label: None,
+                    },
+                    ReceiveInput { label: None },
+                    TimeFor {
+                        duration: 30,
+                        label: None,
+                    },
+                ],
+                &[]
+            )
+        );
+        assert_eq!(Some(Duration::ZERO), total_duration(&[], &[]));
+    }
+
+    #[test]
+    fn nested_loops_unroll() {
+        let (program, variables) = compile(&[
+            TimeFor {
+                duration: 100,
+                label: None,
+            }
+            .into(),
+            ProgramBlock::Loop {
+                count: 3,
+                body: vec![
+                    ProgramBlock::Loop {
+                        count: 2,
+                        body: vec![TimeFor {
+                            duration: 5,
+                            label: None,
+                        }
+                        .into()],
+                    },
+                    TimeFor {
+                        duration: 1,
+                        label: None,
+                    }
+                    .into(),
+                ],
+            },
+        ])
+        .unwrap();
+        assert_eq!(
+            Some(Duration::from_secs(100 + 3 * (2 * 5 + 1))),
+            total_duration(&program, &variables)
+        );
+    }
+
+    #[test]
+    fn remaining_uses_current_counters() {
+        let program = vec![
+            TimeFor {
+                duration: 10,
+                label: None,
+            },
+            OffsetVariable {
+                var_index: 0,
+                offset: -1,
+            },
+            Repeat {
+                to_phase: 0,
+                var_index: 0,
+            },
+        ];
+        assert_eq!(
+            Some(Duration::from_secs(40)),
+            total_duration(&program, &[4])
+        );
+        // After the First Pass's Timer, One Decrement Leaves Three More Passes
+        assert_eq!(Some(Duration::from_secs(30)), timed_from(&program, 1, &[4]));
+    }
+
+    #[test]
+    fn too_long_to_estimate() {
+        let nested = |body: ProgramBlock| ProgramBlock::Loop {
+            count: i8::MAX,
+            body: vec![body],
+        };
+        let second = TimeFor {
+            duration: 1,
+            label: None,
+        };
+        // Two Million Timers Still End, but Walking Them All Would Stall the UI
+        let (program, variables) = compile(&[nested(nested(nested(second.into())))]).unwrap();
+        assert!(check(&program, &variables).is_ok());
+        assert_eq!(None, total_duration(&program, &variables));
+    }
+
+    #[test]
+    fn just_seconds() {
+        assert_eq!("00:00:15", seconds_to_h_m_s_display_string(15));
+    }
+
+    #[test]
+    fn just_minutes() {
+        assert_eq!("00:15:00", seconds_to_h_m_s_display_string(15 * 60));
+    }
+
+    #[test]
+    fn just_hours() {
+        assert_eq!("15:00:00", seconds_to_h_m_s_display_string(15 * 3600));
+    }
+
+    #[test]
+    fn minute_threshold() {
+        assert_eq!("00:01:00", seconds_to_h_m_s_display_string(60));
+    }
+
+    #[test]
+    fn hour_threshold() {
+        assert_eq!("01:00:00", seconds_to_h_m_s_display_string(3600));
+    }
+
+    #[test]
+    fn partial_seconds_round_up() {
+        assert_eq!(3, display_seconds(Duration::from_millis(2001)));
+        assert_eq!(2, display_seconds(Duration::from_secs(2)));
+        assert_eq!(0, display_seconds(Duration::ZERO));
+    }
+
+    #[test]
+    fn program_time() {
+        let mut snapshot = TimerSnapshot {
+            status: TimerStatus::Idle,
+            phase_index: 0,
+            phase: None,
+            remaining: None,
+            elapsed: None,
+            program_remaining: Some(Duration::from_secs(3600)),
+            program_total: Some(Duration::from_secs(3600)),
+            variables: vec![],
+            program_len: 1,
+        };
+        assert_eq!("Program: 01:00:00", program_time_string(&snapshot));
+        snapshot.status = TimerStatus::Running;
+        snapshot.program_remaining = Some(Duration::from_millis(59_500));
+        assert_eq!("Program: 00:01:00 left", program_time_string(&snapshot));
+        snapshot.program_remaining = None;
+        assert_eq!("", program_time_string(&snapshot));
+    }
+}
@@ -0,0 +1,502 @@
+use crate::program_util::Program;
+use crate::validator_util::{validate, Diagnostic, ValidationError};
+use crate::{EditorPhase, PhaseKind, ProgramPhase, TimerFSM};
+
+/**
+ * Loop Count Given to Loops Added in the Editor
+ */
+const DEFAULT_LOOP_COUNT: i8 = 2;
+
+/**
+ * Duration in Seconds Given to Timers Added in the Editor
+ */
+const DEFAULT_DURATION: usize = 60;
+
+/**
+ * The Program Being Edited, Kept Apart From the Running FSM Until It Is Applied
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProgramEditor {
+    program: Program,
+}
+
+impl ProgramEditor {
+    pub fn new(program: Program) -> ProgramEditor {
+        ProgramEditor { program }
+    }
+
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    /**
+     * The Name the Program Is Applied and Saved to the Library Under
+     */
+    pub fn set_name(&mut self, name: &str) {
+        self.program.name = name.trim().to_string();
+    }
+
+    /**
+     * Appends a Phase; a Repeat Comes With the OffsetVariable That Counts Its Passes and, Like
+     * Compiled Loops, a SetVariable Before Its Body That Resets the Counter on Every Entry
+     */
+    pub fn add(&mut self, kind: PhaseKind) {
+        let phases = &mut self.program.phases;
+        let variables = &mut self.program.variables;
+        match kind {
+            PhaseKind::TimeFor => phases.push(ProgramPhase::TimeFor {
+                duration: DEFAULT_DURATION,
+                label: None,
+            }),
+            PhaseKind::ReceiveInput => phases.push(ProgramPhase::ReceiveInput { label: None }),
+            PhaseKind::OffsetVariable => {
+                if variables.is_empty() {
+                    variables.push(DEFAULT_LOOP_COUNT);
+                }
+                phases.push(ProgramPhase::OffsetVariable {
+                    var_index: variables.len() - 1,
+                    offset: -1,
+                });
+            }
+            PhaseKind::Repeat => {
+                variables.push(DEFAULT_LOOP_COUNT);
+                let var_index = variables.len() - 1;
+                self.insert(
+                    0,
+                    ProgramPhase::SetVariable {
+                        var_index,
+                        value: DEFAULT_LOOP_COUNT,
+                    },
+                );
+                let phases = &mut self.program.phases;
+                phases.push(ProgramPhase::OffsetVariable {
+                    var_index,
+                    offset: -1,
+                });
+                phases.push(ProgramPhase::Repeat {
+                    to_phase: 1,
+                    var_index,
+                });
+            }
+            PhaseKind::Other => {}
+        }
+    }
+
+    /**
+     * Inserts a Phase, Keeping Jumps Pointed at the Phases They Pointed at Before
+     */
+    fn insert(&mut self, index: usize, phase: ProgramPhase) {
+        self.program.phases.insert(index, phase);
+        for phase in self.program.phases.iter_mut() {
+            if let ProgramPhase::Repeat { to_phase, .. } = phase {
+                if *to_phase >= index {
+                    *to_phase += 1;
+                }
+            }
+        }
+    }
+
+    /**
+     * Index of the SetVariable Right Before a Loop's Body That Resets Its Counter, if It Has One
+     */
+    fn loop_reset(&self, index: usize) -> Option<usize> {
+        let Some(&ProgramPhase::Repeat {
+            to_phase,
+            var_index,
+        }) = self.program.phases.get(index)
+        else {
+            return None;
+        };
+        let reset = to_phase.checked_sub(1)?;
+        match self.program.phases[reset] {
+            ProgramPhase::SetVariable {
+                var_index: set_index,
+                ..
+            } if set_index == var_index => Some(reset),
+            _ => None,
+        }
+    }
+
+    /**
+     * Removes a Phase, Keeping Jumps Pointed at the Phases They Pointed at Before
+     */
+    pub fn remove(&mut self, index: usize) {
+        if index >= self.program.phases.len() {
+            return;
+        }
+        self.program.phases.remove(index);
+        for phase in self.program.phases.iter_mut() {
+            if let ProgramPhase::Repeat { to_phase, .. } = phase {
+                if *to_phase > index {
+                    *to_phase -= 1;
+                }
+            }
+        }
+    }
+
+    /**
+     * Swaps a Phase With Its Neighbour Above (-1) or Below (1), Following It With Any Jumps
+     */
+    pub fn move_phase(&mut self, index: usize, delta: i32) {
+        let other = index as i64 + delta.signum() as i64;
+        if delta == 0
+            || index >= self.program.phases.len()
+            || other < 0
+            || other as usize >= self.program.phases.len()
+        {
+            return;
+        }
+        let other = other as usize;
+        self.program.phases.swap(index, other);
+        for phase in self.program.phases.iter_mut() {
+            if let ProgramPhase::Repeat { to_phase, .. } = phase {
+                if *to_phase == index {
+                    *to_phase = other;
+                } else if *to_phase == other {
+                    *to_phase = index;
+                }
+            }
+        }
+    }
+
+    /**
+     * Sets the Main Number of a Phase: Seconds, Loop Count or Offset
+     */
+    pub fn set_value(&mut self, index: usize, value: i32) {
+        let variables = &mut self.program.variables;
+        match self.program.phases.get_mut(index) {
+            Some(ProgramPhase::TimeFor { duration, .. }) => *duration = value.max(1) as usize,
+            Some(ProgramPhase::Repeat { var_index, .. }) => {
+                let count = value.clamp(1, i8::MAX as i32) as i8;
+                if let Some(initial) = variables.get_mut(*var_index) {
+                    *initial = count;
+                }
+                if let Some(reset) = self.loop_reset(index) {
+                    if let ProgramPhase::SetVariable { value, .. } = &mut self.program.phases[reset]
+                    {
+                        *value = count;
+                    }
+                }
+            }
+            Some(ProgramPhase::OffsetVariable { offset, .. }) => {
+                *offset = value.clamp(i8::MIN as i32, i8::MAX as i32) as i8;
+            }
+            _ => {}
+        }
+    }
+
+    /**
+     * Sets What a Phase Points at: the Step a Repeat Jumps to or the Counter an Offset Changes
+     *
+     * A loop's counter reset moves with its target, so it still runs right before the body.
+     */
+    pub fn set_target(&mut self, index: usize, target: i32) {
+        if let Some(reset) = self.loop_reset(index) {
+            let target = (target.max(0) as usize).min(index);
+            let reset_phase = self.program.phases[reset].clone();
+            self.remove(reset);
+            let (index, target) = (index - 1, target - usize::from(target > reset));
+            self.insert(target, reset_phase);
+            if let Some(ProgramPhase::Repeat { to_phase, .. }) =
+                self.program.phases.get_mut(index + 1)
+            {
+                *to_phase = target + 1;
+            }
+            return;
+        }
+        let last_variable = self.program.variables.len().saturating_sub(1);
+        match self.program.phases.get_mut(index) {
+            Some(ProgramPhase::Repeat { to_phase, .. }) => {
+                // Repeats Only Jump Back, Which Is All the Validator Can Prove Ends
+                *to_phase = (target.max(0) as usize).min(index);
+            }
+            Some(ProgramPhase::OffsetVariable { var_index, .. }) => {
+                *var_index = (target.max(0) as usize).min(last_variable);
+            }
+            _ => {}
+        }
+    }
+
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        validate(&self.program.phases, &self.program.variables)
+    }
+
+    pub fn build(&self) -> Result<TimerFSM, ValidationError> {
+        self.program.to_fsm()
+    }
+
+    /**
+     * One Row per Phase for the Editor View
+     */
+    pub fn rows(&self) -> Vec<EditorPhase> {
+        self.program
+            .phases
+            .iter()
+            .map(|phase| self.row(phase))
+            .collect()
+    }
+
+    fn row(&self, phase: &ProgramPhase) -> EditorPhase {
+        let titled = |title: &str, label: &Option<String>| match label {
+            Some(label) => format!("{}: {}", title, label),
+            None => title.to_string(),
+        };
+        let (kind, title, value, target) = match phase {
+            ProgramPhase::TimeFor { duration, label } => (
+                PhaseKind::TimeFor,
+                titled("Time", label),
+                *duration as i32,
+                0,
+            ),
+            ProgramPhase::ReceiveInput { label } => {
+                (PhaseKind::ReceiveInput, titled("Input", label), 0, 0)
+            }
+            ProgramPhase::Repeat {
+                to_phase,
+                var_index,
+            } => (
+                PhaseKind::Repeat,
+                format!("Repeat (counter {})", var_index),
+                self.program
+                    .variables
+                    .get(*var_index)
+                    .copied()
+                    .unwrap_or_default() as i32,
+                *to_phase as i32,
+            ),
+            ProgramPhase::OffsetVariable { var_index, offset } => (
+                PhaseKind::OffsetVariable,
+                "Count".to_string(),
+                *offset as i32,
+                *var_index as i32,
+            ),
+            ProgramPhase::SetVariable { var_index, value } => (
+                PhaseKind::Other,
+                format!("Set counter {} to {}", var_index, value),
+                0,
+                0,
+            ),
+            ProgramPhase::PlaySound { sound } => {
+                (PhaseKind::Other, format!("Sound: {}", sound.name()), 0, 0)
+            }
+            ProgramPhase::DisplayText { text } => {
+                (PhaseKind::Other, format!("Text: {}", text), 0, 0)
+            }
+            ProgramPhase::BeginProgram => (PhaseKind::Other, "Begin".to_string(), 0, 0),
+            ProgramPhase::EndProgram => (PhaseKind::Other, "End".to_string(), 0, 0),
+        };
+        EditorPhase {
+            kind,
+            title: title.into(),
+            value,
+            target,
+        }
+    }
+
+    /**
+     * A One Line Summary of the Diagnostics, Shown Under the Editor
+     */
+    pub fn status(&self) -> String {
+        let diagnostics = self.diagnostics();
+        if diagnostics.is_empty() {
+            "Program is valid".to_string()
+        } else {
+            diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.to_string())
+                .collect::<Vec<String>>()
+                .join("; ")
+        }
+    }
+}
+
+#[cfg(test)]
+mod editor_util_tests {
+
+    use super::ProgramEditor;
+    use crate::program_util::Program;
+    use crate::ProgramPhase::*;
+    use crate::{PhaseKind, TimerInput, TimerStatus};
+
+    fn example_editor() -> ProgramEditor {
+        ProgramEditor::new(Program::new(
+            "Example",
+            vec![
+                ReceiveInput { label: None },
+                TimeFor {
+                    duration: 5,
+                    label: None,
+                },
+                OffsetVariable {
+                    var_index: 0,
+                    offset: -1,
+                },
+                Repeat {
+                    to_phase: 1,
+                    var_index: 0,
+                },
+            ],
+            vec![3],
+        ))
+    }
+
+    #[test]
+    fn add_loop() {
+        let mut editor = ProgramEditor::new(Program::new("Empty", vec![], vec![]));
+        editor.add(PhaseKind::TimeFor);
+        editor.add(PhaseKind::Repeat);
+        assert_eq!(
+            vec![
+                SetVariable {
+                    var_index: 0,
+                    value: 2,
+                },
+                TimeFor {
+                    duration: 60,
+                    label: None,
+                },
+                OffsetVariable {
+                    var_index: 0,
+                    offset: -1,
+                },
+                Repeat {
+                    to_phase: 1,
+                    var_index: 0,
+                },
+            ],
+            editor.program().phases
+        );
+        assert_eq!(vec![2], editor.program().variables);
+        assert!(editor.build().is_ok());
+    }
+
+    #[test]
+    fn nested_loop_resets_on_entry() {
+        let mut editor = ProgramEditor::new(Program::new("Empty", vec![], vec![]));
+        editor.add(PhaseKind::ReceiveInput);
+        editor.add(PhaseKind::Repeat);
+        editor.set_value(3, 3);
+        editor.add(PhaseKind::Repeat);
+        // The Outer Loop Wraps the Inner One, Counter Reset Included
+        assert_eq!(
+            SetVariable {
+                var_index: 1,
+                value: 2,
+            },
+            editor.program().phases[0]
+        );
+        assert_eq!(
+            Repeat {
+                to_phase: 2,
+                var_index: 0,
+            },
+            editor.program().phases[4]
+        );
+
+        let mut model = editor.build().unwrap();
+        model.input(TimerInput::Start);
+        let mut inputs = 0;
+        while model.status() == TimerStatus::AwaitingInput {
+            model.input(TimerInput::Input);
+            inputs += 1;
+        }
+        assert_eq!(6, inputs);
+    }
+
+    #[test]
+    fn loop_reset_follows_target() {
+        let mut editor = ProgramEditor::new(Program::new("Empty", vec![], vec![]));
+        editor.add(PhaseKind::TimeFor);
+        editor.add(PhaseKind::ReceiveInput);
+        editor.add(PhaseKind::Repeat);
+        editor.set_value(4, 5);
+        editor.set_target(4, 2);
+        assert_eq!(
+            vec![
+                TimeFor {
+                    duration: 60,
+                    label: None,
+                },
+                SetVariable {
+                    var_index: 0,
+                    value: 5,
+                },
+                ReceiveInput { label: None },
+                OffsetVariable {
+                    var_index: 0,
+                    offset: -1,
+                },
+                Repeat {
+                    to_phase: 2,
+                    var_index: 0,
+                },
+            ],
+            editor.program().phases
+        );
+        assert_eq!(vec![5], editor.program().variables);
+        assert_eq!("Program is valid", editor.status());
+    }
+
+    #[test]
+    fn remove_keeps_jumps() {
+        let mut editor = example_editor();
+        editor.remove(0);
+        assert_eq!(
+            Repeat {
+                to_phase: 0,
+                var_index: 0,
+            },
+            editor.program().phases[2]
+        );
+        assert_eq!("Program is valid", editor.status());
+    }
+
+    #[test]
+    fn move_follows_jumps() {
+        let mut editor = example_editor();
+        editor.move_phase(1, -1);
+        assert_eq!(
+            Repeat {
+                to_phase: 0,
+                var_index: 0,
+            },
+            editor.program().phases[3]
+        );
+        // Moving Past Either End Does Nothing
+        editor.move_phase(0, -1);
+        editor.move_phase(3, 1);
+        assert_eq!(
+            TimeFor {
+                duration: 5,
+                label: None,
+            },
+            editor.program().phases[0]
+        );
+    }
+
+    #[test]
+    fn edit_values() {
+        let mut editor = example_editor();
+        editor.set_value(1, 90);
+        editor.set_value(3, 500);
+        editor.set_target(3, 40);
+        let rows = editor.rows();
+        assert_eq!(90, rows[1].value);
+        assert_eq!(127, rows[3].value);
+        assert_eq!(3, rows[3].target);
+        assert_eq!(vec![127], editor.program().variables);
+    }
+
+    #[test]
+    fn invalid_edits_are_reported() {
+        let mut editor = example_editor();
+        editor.remove(1);
+        editor.remove(0);
+        editor.set_value(0, 1);
+        assert_eq!(
+            "phase 1: loop back to phase 0 never ends, its counter never reaches zero",
+            editor.status()
+        );
+        assert!(editor.build().is_err());
+    }
+}
@@ -0,0 +1,252 @@
+use crate::history_util::{History, HistoryError};
+use crate::stats_util::{phase_records, PhaseRecord};
+use crate::store_util::StoreError;
+use crate::ExportFormat;
+
+use serde::Serialize;
+
+/**
+ * Stamped on JSON Exports for Whatever Reads Them Next; the App Itself Never Imports an Export
+ */
+pub const EXPORT_FORMAT_VERSION: u32 = 1;
+
+const CSV_HEADER: &str =
+    "program,run_started_at,phase_label,planned_seconds,elapsed_seconds,status,pauses";
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv",
+            ExportFormat::Json => "application/json",
+        }
+    }
+
+    pub fn file_name(&self) -> String {
+        format!("oxidoro-history.{}", self.extension())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct ExportedPause {
+    start: u64,
+    end: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct ExportedPhase {
+    label: Option<String>,
+    planned_seconds: u64,
+    /// Time Spent Counting Down, Leaving Out Pauses
+    elapsed_seconds: f64,
+    /// `completed`, `skipped` or `stopped`, Like the CSV Status Column
+    status: String,
+    pauses: Vec<ExportedPause>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct ExportedRun {
+    program: String,
+    started_at: u64,
+    ended_at: u64,
+    finished: bool,
+    phases: Vec<ExportedPhase>,
+}
+
+#[derive(Serialize)]
+struct ExportFile {
+    version: u32,
+    runs: Vec<ExportedRun>,
+}
+
+impl From<PhaseRecord> for ExportedPhase {
+    fn from(record: PhaseRecord) -> ExportedPhase {
+        ExportedPhase {
+            label: record.label,
+            planned_seconds: record.planned.as_secs(),
+            elapsed_seconds: record.active.as_secs_f64(),
+            status: record.outcome.as_str().to_string(),
+            pauses: record
+                .pauses
+                .into_iter()
+                .map(|(start, end)| ExportedPause { start, end })
+                .collect(),
+        }
+    }
+}
+
+/**
+ * Quotes a CSV Field Only When It Holds a Comma, Quote or Line Break
+ */
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/**
+ * One Row per Timed Phase; Pauses Are `start-end` Pairs in Unix Milliseconds, Separated by `;`
+ */
+pub fn to_csv(history: &History) -> String {
+    let mut csv = format!("{}\n", CSV_HEADER);
+    for run in &history.runs {
+        for record in phase_records(run) {
+            let pauses = record
+                .pauses
+                .iter()
+                .map(|(start, end)| format!("{}-{}", start, end))
+                .collect::<Vec<String>>()
+                .join(";");
+            csv += &[
+                csv_field(&run.program),
+                run.started_at.to_string(),
+                csv_field(record.label.as_deref().unwrap_or_default()),
+                record.planned.as_secs().to_string(),
+                format!("{:.3}", record.active.as_secs_f64()),
+                record.outcome.as_str().to_string(),
+                pauses,
+            ]
+            .join(",");
+            csv.push('\n');
+        }
+    }
+    csv
+}
+
+/**
+ * Runs With Their Timed Phases Nested Inside, Rather Than the Raw Event Log Kept in Storage
+ */
+pub fn to_json(history: &History) -> Result<String, HistoryError> {
+    let runs = history
+        .runs
+        .iter()
+        .map(|run| ExportedRun {
+            program: run.program.clone(),
+            started_at: run.started_at,
+            ended_at: run.ended_at,
+            finished: run.finished,
+            phases: phase_records(run).into_iter().map(Into::into).collect(),
+        })
+        .collect();
+    serde_json::to_string_pretty(&ExportFile {
+        version: EXPORT_FORMAT_VERSION,
+        runs,
+    })
+    .map_err(HistoryError::Json)
+}
+
+pub fn export(history: &History, format: ExportFormat) -> Result<String, HistoryError> {
+    match format {
+        ExportFormat::Csv => Ok(to_csv(history)),
+        ExportFormat::Json => to_json(history),
+    }
+}
+
+/**
+ * Writes the Export Next to the Saved History, Returning Where It Went
+ */
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_export(history: &History, format: ExportFormat) -> Result<String, HistoryError> {
+    let path = crate::store_util::data_dir()
+        .map(|dir| dir.join(format.file_name()))
+        .unwrap_or_else(|| std::path::PathBuf::from(format.file_name()));
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(StoreError::Io)?;
+    }
+    std::fs::write(&path, export(history, format)?).map_err(StoreError::Io)?;
+    Ok(path.display().to_string())
+}
+
+/**
+ * Hands the Export to the Browser as a Download, Returning the File Name
+ */
+#[cfg(target_arch = "wasm32")]
+pub fn save_export(history: &History, format: ExportFormat) -> Result<String, HistoryError> {
+    use wasm_bindgen::JsCast;
+
+    let storage_error = |err: wasm_bindgen::JsValue| StoreError::Storage(format!("{:?}", err));
+    let contents = export(history, format)?;
+    let parts = js_sys::Array::of1(&wasm_bindgen::JsValue::from_str(&contents));
+    let mut options = web_sys::BlobPropertyBag::new();
+    options.type_(format.mime_type());
+    let blob = web_sys::Blob::new_with_str_sequence_and_options(&parts, &options)
+        .map_err(storage_error)?;
+    let url = web_sys::Url::create_object_url_with_blob(&blob).map_err(storage_error)?;
+
+    let document = web_sys::window()
+        .and_then(|window| window.document())
+        .ok_or_else(|| StoreError::Storage("document is not available".into()))?;
+    let link: web_sys::HtmlAnchorElement = document
+        .create_element("a")
+        .map_err(storage_error)?
+        .dyn_into()
+        .map_err(|element| storage_error(element.into()))?;
+    link.set_href(&url);
+    link.set_download(&format.file_name());
+    link.click();
+    // Revoking Right Away Can Cancel the Download in Firefox and Safari, so Wait a Minute
+    let revoke = wasm_bindgen::closure::Closure::once_into_js(move || {
+        let _ = web_sys::Url::revoke_object_url(&url);
+    });
+    web_sys::window()
+        .ok_or_else(|| StoreError::Storage("window is not available".into()))?
+        .set_timeout_with_callback_and_timeout_and_arguments_0(revoke.unchecked_ref(), 60_000)
+        .map_err(storage_error)?;
+    Ok(format.file_name())
+}
+
+#[cfg(test)]
+mod export_util_tests {
+
+    use super::{csv_field, to_csv, to_json};
+    use crate::history_util::history_fixtures::{example_run, timer};
+    use crate::history_util::{History, HistoryEvent};
+
+    fn example_history() -> History {
+        History {
+            runs: vec![example_run(1_000)],
+        }
+    }
+
+    #[test]
+    fn csv_rows() {
+        assert_eq!(
+            "program,run_started_at,phase_label,planned_seconds,elapsed_seconds,status,pauses\n\
+             \"Work, Rest\",1000,Focus,60,60.000,completed,21000-31000\n\
+             \"Work, Rest\",1000,Break,30,5.000,skipped,\n",
+            to_csv(&example_history())
+        );
+        assert_eq!("\"say \"\"hi\"\"\"", csv_field("say \"hi\""));
+
+        let mut stopped = example_run(1_000);
+        stopped.events[4].event = HistoryEvent::Stopped {
+            phase: timer(30, "Break"),
+        };
+        assert!(to_csv(&History {
+            runs: vec![stopped]
+        })
+        .ends_with("\"Work, Rest\",1000,Break,30,5.000,stopped,\n"));
+    }
+
+    #[test]
+    fn json_nests_phases() {
+        let json: serde_json::Value =
+            serde_json::from_str(&to_json(&example_history()).unwrap()).unwrap();
+        assert_eq!(1, json["version"]);
+        let phases = &json["runs"][0]["phases"];
+        assert_eq!("Focus", phases[0]["label"]);
+        assert_eq!(60, phases[0]["planned_seconds"]);
+        assert_eq!(60.0, phases[0]["elapsed_seconds"]);
+        assert_eq!(21_000, phases[0]["pauses"][0]["start"]);
+        assert_eq!("skipped", phases[1]["status"]);
+        assert_eq!(true, json["runs"][0]["finished"]);
+    }
+}
@@ -0,0 +1,561 @@
+use crate::store_util::{self, Store, StoreError};
+use crate::ProgramPhase;
+use crate::TimerOutput;
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/**
+ * Version of the Saved History; Runs Saved by a Newer Build Are Left on Disk Rather Than Rewritten
+ */
+pub const HISTORY_FORMAT_VERSION: u32 = 1;
+
+/**
+ * Something That Happened During a Run, Recorded From the FSM's Outputs
+ */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum HistoryEvent {
+    PhaseChange {
+        prev_phase: ProgramPhase,
+        next_phase: ProgramPhase,
+        completed: bool,
+    },
+    Paused,
+    Resumed,
+    Reset,
+    Stopped {
+        phase: ProgramPhase,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Wall Clock Time, in Milliseconds Since the Unix Epoch
+    pub at: u64,
+    #[serde(flatten)]
+    pub event: HistoryEvent,
+}
+
+/**
+ * One Run of a Program, From Start Until It Finished or Was Stopped
+ */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Run {
+    pub program: String,
+    pub started_at: u64,
+    pub ended_at: u64,
+    /// False if the Run Was Stopped Before the Program Ended
+    pub finished: bool,
+    pub events: Vec<HistoryEntry>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct History {
+    pub runs: Vec<Run>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct HistoryFile {
+    version: u32,
+    #[serde(flatten)]
+    history: History,
+}
+
+#[derive(Debug)]
+pub enum HistoryError {
+    Json(serde_json::Error),
+    Store(StoreError),
+    UnsupportedVersion(u32),
+    /// The Saved History Failed to Load, so Saving Would Overwrite Runs That Are Still on Disk
+    Unreadable,
+}
+
+impl fmt::Display for HistoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HistoryError::Json(error) => write!(f, "invalid history JSON: {}", error),
+            HistoryError::Store(error) => write!(f, "{}", error),
+            HistoryError::UnsupportedVersion(version) => write!(
+                f,
+                "history format version {} is newer than the supported version {}",
+                version, HISTORY_FORMAT_VERSION
+            ),
+            HistoryError::Unreadable => write!(
+                f,
+                "the saved history could not be read, so new runs are not saved over it"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for HistoryError {}
+
+impl From<StoreError> for HistoryError {
+    fn from(error: StoreError) -> HistoryError {
+        HistoryError::Store(error)
+    }
+}
+
+impl History {
+    pub fn to_json(&self) -> Result<String, HistoryError> {
+        serde_json::to_string_pretty(&HistoryFile {
+            version: HISTORY_FORMAT_VERSION,
+            history: self.clone(),
+        })
+        .map_err(HistoryError::Json)
+    }
+
+    pub fn from_json(source: &str) -> Result<History, HistoryError> {
+        let file: HistoryFile = serde_json::from_str(source).map_err(HistoryError::Json)?;
+        if file.version > HISTORY_FORMAT_VERSION {
+            return Err(HistoryError::UnsupportedVersion(file.version));
+        }
+        Ok(file.history)
+    }
+
+    /**
+     * The Saved History, or an Empty One if Nothing Was Saved Yet
+     */
+    pub fn load(store: &dyn Store) -> Result<History, HistoryError> {
+        match store.load()? {
+            Some(source) => History::from_json(&source),
+            None => Ok(History::default()),
+        }
+    }
+
+    pub fn save(&self, store: &dyn Store) -> Result<(), HistoryError> {
+        Ok(store.save(&self.to_json()?)?)
+    }
+}
+
+/**
+ * `history.json` in the Data Directory, or the `oxidoro-history` localStorage Key
+ */
+pub fn default_store() -> Box<dyn Store> {
+    store_util::default_store("history.json")
+}
+
+/**
+ * Milliseconds Since the Unix Epoch, Which Also Works in the Browser
+ */
+pub fn unix_millis() -> u64 {
+    web_time::SystemTime::now()
+        .duration_since(web_time::UNIX_EPOCH)
+        .map(|since| since.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/**
+ * Turns the FSM's Outputs Into Runs, Saving the History Whenever a Run Ends
+ */
+pub struct HistoryRecorder {
+    history: History,
+    current: Option<Run>,
+    program: String,
+    store: Box<dyn Store>,
+    unreadable: bool,
+}
+
+impl HistoryRecorder {
+    /**
+     * Starts With an Empty History; Call `load` to Pick Up What the Store Has Saved
+     */
+    pub fn new(store: Box<dyn Store>, program: &str) -> HistoryRecorder {
+        HistoryRecorder {
+            history: History::default(),
+            current: None,
+            program: program.into(),
+            store,
+            unreadable: false,
+        }
+    }
+
+    /**
+     * Picks Up the Saved History; If It Cannot Be Read, Runs Are Still Recorded but Never Saved
+     * Over It
+     */
+    pub fn load(&mut self) -> Result<(), HistoryError> {
+        let loaded = History::load(self.store.as_ref());
+        self.unreadable = loaded.is_err();
+        self.history = loaded?;
+        Ok(())
+    }
+
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    pub fn program(&self) -> &str {
+        &self.program
+    }
+
+    /**
+     * Names the Program Later Runs Belong to, e.g. After a New One Is Applied in the Editor
+     *
+     * A run still open belongs to the old program, so it is closed as stopped first.
+     */
+    pub fn set_program(&mut self, program: &str) -> Result<(), HistoryError> {
+        let closed = self.close();
+        self.program = program.into();
+        closed
+    }
+
+    pub fn close(&mut self) -> Result<(), HistoryError> {
+        self.close_at(unix_millis())
+    }
+
+    /**
+     * Ends the Open Run as Stopped in the Phase It Was in, for When the FSM Goes Away Mid-Run
+     */
+    pub fn close_at(&mut self, at: u64) -> Result<(), HistoryError> {
+        let Some(run) = &self.current else {
+            return Ok(());
+        };
+        let program_phase = run
+            .events
+            .iter()
+            .rev()
+            .find_map(|entry| match &entry.event {
+                HistoryEvent::PhaseChange { next_phase, .. } => Some(next_phase.clone()),
+                _ => None,
+            })
+            .unwrap_or(ProgramPhase::EndProgram);
+        self.record_at(&TimerOutput::ProgramStopped { program_phase }, at)
+    }
+
+    pub fn record(&mut self, output: &TimerOutput) -> Result<(), HistoryError> {
+        self.record_at(output, unix_millis())
+    }
+
+    /**
+     * Records an Output Produced at `at`, in Milliseconds Since the Unix Epoch
+     */
+    pub fn record_at(&mut self, output: &TimerOutput, at: u64) -> Result<(), HistoryError> {
+        let event = match output {
+            TimerOutput::PhaseChange {
+                prev_phase,
+                next_phase,
+                phase_completed,
+            } => {
+                if *prev_phase == ProgramPhase::BeginProgram {
+                    self.current = Some(Run {
+                        program: self.program.clone(),
+                        started_at: at,
+                        ended_at: at,
+                        finished: false,
+                        events: vec![],
+                    });
+                }
+                HistoryEvent::PhaseChange {
+                    prev_phase: prev_phase.clone(),
+                    next_phase: next_phase.clone(),
+                    completed: *phase_completed,
+                }
+            }
+            TimerOutput::TimerPaused => HistoryEvent::Paused,
+            TimerOutput::TimerResumed { .. } => HistoryEvent::Resumed,
+            TimerOutput::TimerReset { .. } => HistoryEvent::Reset,
+            TimerOutput::ProgramStopped { program_phase } => HistoryEvent::Stopped {
+                phase: program_phase.clone(),
+            },
+            _ => return Ok(()),
+        };
+        let finished = matches!(
+            event,
+            HistoryEvent::PhaseChange {
+                next_phase: ProgramPhase::EndProgram,
+                ..
+            }
+        );
+        let stopped = matches!(event, HistoryEvent::Stopped { .. });
+
+        // Stopping While Idle or Pausing Nothing Is Not Part of Any Run
+        let Some(run) = self.current.as_mut() else {
+            return Ok(());
+        };
+        run.events.push(HistoryEntry { at, event });
+        run.ended_at = at;
+        if finished || stopped {
+            let mut run = self.current.take().unwrap();
+            run.finished = finished;
+            self.history.runs.push(run);
+            if self.unreadable {
+                return Err(HistoryError::Unreadable);
+            }
+            self.history.save(self.store.as_ref())?;
+        }
+        Ok(())
+    }
+}
+
+/**
+ * Histories Shared by the Tests of the Modules That Read Them, Like Stats and Export
+ */
+#[cfg(test)]
+pub(crate) mod history_fixtures {
+
+    use super::{HistoryEntry, HistoryEvent, Run};
+    use crate::ProgramPhase::{self, *};
+
+    pub fn timer(duration: usize, label: &str) -> ProgramPhase {
+        TimeFor {
+            duration,
+            label: Some(label.into()),
+        }
+    }
+
+    pub fn change(
+        at: u64,
+        prev_phase: ProgramPhase,
+        next_phase: ProgramPhase,
+        completed: bool,
+    ) -> HistoryEntry {
+        HistoryEntry {
+            at,
+            event: HistoryEvent::PhaseChange {
+                prev_phase,
+                next_phase,
+                completed,
+            },
+        }
+    }
+
+    /**
+     * A Run Starting at `start`: a 60s Focus Timer Paused for 10s, Then a Break Skipped After 5s
+     */
+    pub fn example_run(start: u64) -> Run {
+        Run {
+            program: "Work, Rest".into(),
+            started_at: start,
+            ended_at: start + 75_000,
+            finished: true,
+            events: vec![
+                change(start, BeginProgram, timer(60, "Focus"), true),
+                HistoryEntry {
+                    at: start + 20_000,
+                    event: HistoryEvent::Paused,
+                },
+                HistoryEntry {
+                    at: start + 30_000,
+                    event: HistoryEvent::Resumed,
+                },
+                change(start + 70_000, timer(60, "Focus"), timer(30, "Break"), true),
+                change(start + 75_000, timer(30, "Break"), EndProgram, false),
+            ],
+        }
+    }
+}
+
+#[cfg(test)]
+mod history_util_tests {
+
+    use super::{History, HistoryError, HistoryEvent, HistoryRecorder};
+    use crate::store_util::{MemoryStore, Store};
+    use crate::ProgramPhase::*;
+    use crate::TimerOutput::*;
+    use std::time::Duration;
+
+    fn timer() -> crate::ProgramPhase {
+        TimeFor {
+            duration: 10,
+            label: None,
+        }
+    }
+
+    #[test]
+    fn records_finished_run() {
+        let store = MemoryStore::default();
+        let mut recorder = HistoryRecorder::new(Box::new(store.clone()), "Test");
+        recorder.load().unwrap();
+        let outputs = [
+            PhaseChange {
+                prev_phase: BeginProgram,
+                next_phase: timer(),
+                phase_completed: true,
+            },
+            TimerProgress {
+                remaining: Duration::from_secs(5),
+            },
+            TimerPaused,
+            TimerResumed {
+                remaining: Duration::from_secs(5),
+            },
+            PhaseChange {
+                prev_phase: timer(),
+                next_phase: EndProgram,
+                phase_completed: true,
+            },
+        ];
+        for (at, output) in outputs.iter().enumerate() {
+            recorder.record_at(output, 1000 * at as u64).unwrap();
+        }
+        let history = History::load(&store).unwrap();
+        assert_eq!(1, history.runs.len());
+        let run = &history.runs[0];
+        assert_eq!("Test", run.program);
+        assert!(run.finished);
+        assert_eq!((0, 4000), (run.started_at, run.ended_at));
+        // Progress Ticks Are Not Worth Keeping
+        assert_eq!(4, run.events.len());
+        assert_eq!(HistoryEvent::Paused, run.events[1].event);
+    }
+
+    #[test]
+    fn unreadable_history_is_not_overwritten() {
+        let store = MemoryStore::default();
+        store.save("{ not json").unwrap();
+        let mut recorder = HistoryRecorder::new(Box::new(store.clone()), "Test");
+        assert!(matches!(recorder.load(), Err(HistoryError::Json(_))));
+        recorder
+            .record_at(
+                &PhaseChange {
+                    prev_phase: BeginProgram,
+                    next_phase: timer(),
+                    phase_completed: true,
+                },
+                0,
+            )
+            .unwrap();
+        let saved = recorder.record_at(
+            &PhaseChange {
+                prev_phase: timer(),
+                next_phase: EndProgram,
+                phase_completed: true,
+            },
+            1000,
+        );
+        assert!(matches!(saved, Err(HistoryError::Unreadable)));
+        assert_eq!(Some("{ not json".to_string()), store.load().unwrap());
+        // The Run Still Counts for This Session
+        assert_eq!(1, recorder.history().runs.len());
+    }
+
+    #[test]
+    fn records_skips_and_stops() {
+        let mut recorder = HistoryRecorder::new(Box::new(MemoryStore::default()), "Test");
+        recorder
+            .record_at(
+                &ProgramStopped {
+                    program_phase: EndProgram,
+                },
+                0,
+            )
+            .unwrap();
+        assert!(recorder.history().runs.is_empty());
+
+        for output in [
+            PhaseChange {
+                prev_phase: BeginProgram,
+                next_phase: timer(),
+                phase_completed: true,
+            },
+            PhaseChange {
+                prev_phase: timer(),
+                next_phase: ReceiveInput { label: None },
+                phase_completed: false,
+            },
+            ProgramStopped {
+                program_phase: ReceiveInput { label: None },
+            },
+        ] {
+            recorder.record_at(&output, 0).unwrap();
+        }
+        let run = &recorder.history().runs[0];
+        assert!(!run.finished);
+        assert_eq!(
+            HistoryEvent::PhaseChange {
+                prev_phase: timer(),
+                next_phase: ReceiveInput { label: None },
+                completed: false,
+            },
+            run.events[1].event
+        );
+    }
+
+    #[test]
+    fn switching_program_closes_open_run() {
+        let store = MemoryStore::default();
+        let mut recorder = HistoryRecorder::new(Box::new(store.clone()), "Old");
+        recorder
+            .record_at(
+                &PhaseChange {
+                    prev_phase: BeginProgram,
+                    next_phase: timer(),
+                    phase_completed: true,
+                },
+                0,
+            )
+            .unwrap();
+        recorder.set_program("New").unwrap();
+        assert_eq!("New", recorder.program());
+
+        let history = History::load(&store).unwrap();
+        assert_eq!(1, history.runs.len());
+        let run = &history.runs[0];
+        assert_eq!("Old", run.program);
+        assert!(!run.finished);
+        assert_eq!(
+            HistoryEvent::Stopped { phase: timer() },
+            run.events.last().unwrap().event
+        );
+
+        // Nothing Is Open Anymore, so a Stop From the New Program's Idle FSM Is Ignored
+        recorder
+            .record_at(
+                &ProgramStopped {
+                    program_phase: EndProgram,
+                },
+                1000,
+            )
+            .unwrap();
+        assert_eq!(1, recorder.history().runs.len());
+    }
+
+    #[test]
+    fn json_layout() {
+        let mut recorder = HistoryRecorder::new(Box::new(MemoryStore::default()), "Test");
+        recorder
+            .record_at(
+                &PhaseChange {
+                    prev_phase: BeginProgram,
+                    next_phase: EndProgram,
+                    phase_completed: true,
+                },
+                7,
+            )
+            .unwrap();
+        let json: serde_json::Value =
+            serde_json::from_str(&recorder.history().to_json().unwrap()).unwrap();
+        assert_eq!(1, json["version"]);
+        assert_eq!("phase_change", json["runs"][0]["events"][0]["type"]);
+        assert_eq!(7, json["runs"][0]["events"][0]["at"]);
+        assert_eq!(
+            recorder.history(),
+            &History::from_json(&recorder.history().to_json().unwrap()).unwrap()
+        );
+        assert!(matches!(
+            History::from_json(r#"{"version": 99, "runs": []}"#),
+            Err(HistoryError::UnsupportedVersion(99))
+        ));
+    }
+
+    #[test]
+    fn nothing_saved_yet() {
+        let store = MemoryStore::default();
+        assert_eq!(History::default(), History::load(&store).unwrap());
+
+        let mut recorder = HistoryRecorder::new(Box::new(store.clone()), "Test");
+        recorder
+            .record_at(
+                &PhaseChange {
+                    prev_phase: BeginProgram,
+                    next_phase: EndProgram,
+                    phase_completed: true,
+                },
+                0,
+            )
+            .unwrap();
+        assert_eq!(recorder.history(), &History::load(&store).unwrap());
+    }
+}
@@ -0,0 +1,212 @@
+use crate::store_util::{self, StoreError};
+use crate::{TimerInput, TimerStatus};
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/**
+ * What a Shortcut Does; Everything but TogglePause Is Sent as the TimerInput of the Same Name
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyAction {
+    Start,
+    Stop,
+    Pause,
+    Resume,
+    /// Pauses a Running Timer and Resumes a Paused One
+    TogglePause,
+    Reset,
+    Skip,
+    Input,
+}
+
+/**
+ * Names for Keys That Do Not Type a Printable Character, and the Text Slint Reports for Them
+ */
+const NAMED_KEYS: [(&str, &str); 5] = [
+    ("space", " "),
+    ("enter", "\n"),
+    ("escape", "\u{1b}"),
+    ("tab", "\t"),
+    ("backspace", "\u{8}"),
+];
+
+#[derive(Debug)]
+pub enum KeymapError {
+    Toml(toml::de::Error),
+    Store(StoreError),
+    UnknownKey(String),
+}
+
+impl fmt::Display for KeymapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeymapError::Toml(error) => write!(f, "invalid keymap TOML: {}", error),
+            KeymapError::Store(error) => write!(f, "{}", error),
+            KeymapError::UnknownKey(key) => write!(
+                f,
+                "'{}' is not a key, use a single character or one of: {}",
+                key,
+                NAMED_KEYS.map(|(name, _)| name).join(", ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for KeymapError {}
+
+impl From<StoreError> for KeymapError {
+    fn from(error: StoreError) -> KeymapError {
+        KeymapError::Store(error)
+    }
+}
+
+/**
+ * Key Text as Slint Reports It, Lower Cased so Shift Does Not Change a Letter's Binding
+ */
+fn key_text(name: &str) -> Result<String, KeymapError> {
+    let lower = name.to_lowercase();
+    if let Some((_, text)) = NAMED_KEYS.iter().find(|(key, _)| *key == lower) {
+        return Ok(text.to_string());
+    }
+    match lower.chars().count() {
+        1 => Ok(lower),
+        _ => Err(KeymapError::UnknownKey(name.to_string())),
+    }
+}
+
+/**
+ * Keyboard Shortcuts for the Timer View, Keyed by the Text of the Pressed Key
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keymap {
+    bindings: BTreeMap<String, KeyAction>,
+}
+
+impl Default for Keymap {
+    fn default() -> Keymap {
+        Keymap::from_pairs(&[
+            ("space", KeyAction::TogglePause),
+            ("enter", KeyAction::Input),
+            ("s", KeyAction::Start),
+            ("escape", KeyAction::Stop),
+            ("n", KeyAction::Skip),
+            ("r", KeyAction::Reset),
+        ])
+        .unwrap()
+    }
+}
+
+impl Keymap {
+    pub fn from_pairs(pairs: &[(&str, KeyAction)]) -> Result<Keymap, KeymapError> {
+        let mut bindings = BTreeMap::new();
+        for (name, action) in pairs {
+            bindings.insert(key_text(name)?, *action);
+        }
+        Ok(Keymap { bindings })
+    }
+
+    /**
+     * A Table of Key Names to Actions, e.g. `space = "toggle_pause"`; It Replaces the Defaults
+     */
+    pub fn from_toml(source: &str) -> Result<Keymap, KeymapError> {
+        let table: BTreeMap<String, KeyAction> =
+            toml::from_str(source).map_err(KeymapError::Toml)?;
+        let pairs: Vec<(&str, KeyAction)> = table
+            .iter()
+            .map(|(name, action)| (name.as_str(), *action))
+            .collect();
+        Keymap::from_pairs(&pairs)
+    }
+
+    pub fn action(&self, text: &str) -> Option<KeyAction> {
+        self.bindings.get(&text.to_lowercase()).copied()
+    }
+
+    /**
+     * The Input a Key Press Sends, Given the Timer's Status; None if the Key Is Not Bound
+     */
+    pub fn input_for(&self, text: &str, status: TimerStatus) -> Option<TimerInput> {
+        Some(match self.action(text)? {
+            KeyAction::Start => TimerInput::Start,
+            KeyAction::Stop => TimerInput::Stop,
+            KeyAction::Pause => TimerInput::Pause,
+            KeyAction::Resume => TimerInput::Resume,
+            KeyAction::TogglePause => match status {
+                TimerStatus::Running => TimerInput::Pause,
+                TimerStatus::Paused => TimerInput::Resume,
+                TimerStatus::Idle | TimerStatus::AwaitingInput => return None,
+            },
+            KeyAction::Reset => TimerInput::Reset,
+            KeyAction::Skip => TimerInput::Skip,
+            KeyAction::Input => TimerInput::Input,
+        })
+    }
+}
+
+/**
+ * The User's Keymap From `keys.toml` in the Data Directory (the `oxidoro-keys` localStorage Key
+ * in the Browser), or the Defaults if They Have Not Written One
+ */
+pub fn load_keymap() -> Result<Keymap, KeymapError> {
+    match store_util::default_store("keys.toml").load()? {
+        Some(source) => Keymap::from_toml(&source),
+        None => Ok(Keymap::default()),
+    }
+}
+
+#[cfg(test)]
+mod keymap_util_tests {
+
+    use super::{KeyAction, Keymap, KeymapError};
+    use crate::{TimerInput, TimerStatus};
+
+    #[test]
+    fn default_bindings() {
+        let keymap = Keymap::default();
+        assert_eq!(
+            Some(TimerInput::Pause),
+            keymap.input_for(" ", TimerStatus::Running)
+        );
+        assert_eq!(
+            Some(TimerInput::Resume),
+            keymap.input_for(" ", TimerStatus::Paused)
+        );
+        assert_eq!(None, keymap.input_for(" ", TimerStatus::Idle));
+        assert_eq!(
+            Some(TimerInput::Input),
+            keymap.input_for("\n", TimerStatus::AwaitingInput)
+        );
+        // Shift Does Not Matter
+        assert_eq!(
+            Some(TimerInput::Skip),
+            keymap.input_for("N", TimerStatus::Running)
+        );
+        assert_eq!(None, keymap.input_for("q", TimerStatus::Running));
+    }
+
+    #[test]
+    fn toml_replaces_defaults() {
+        let keymap = Keymap::from_toml(
+            r#"
+            p = "pause"
+            Enter = "skip"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(Some(KeyAction::Pause), keymap.action("p"));
+        assert_eq!(Some(KeyAction::Skip), keymap.action("\n"));
+        assert_eq!(None, keymap.action(" "));
+
+        assert!(matches!(
+            Keymap::from_toml("pgup = \"skip\""),
+            Err(KeymapError::UnknownKey(_))
+        ));
+        assert!(matches!(
+            Keymap::from_toml("p = \"jump\""),
+            Err(KeymapError::Toml(_))
+        ));
+    }
+}
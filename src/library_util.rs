@@ -0,0 +1,329 @@
+use crate::program_util::Program;
+use crate::store_util::{self, Store, StoreError};
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/**
+ * Version of the Saved User Programs; a Newer One Fails to Load and Saving Is Refused Until It Does
+ */
+pub const LIBRARY_FORMAT_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct LibraryFile {
+    version: u32,
+    programs: Vec<Program>,
+}
+
+#[derive(Debug)]
+pub enum LibraryError {
+    Json(serde_json::Error),
+    Store(StoreError),
+    UnsupportedVersion(u32),
+    EmptyName,
+    BuiltIn(String),
+    UnknownProgram(String),
+    /// The Saved Programs Failed to Load, so Saving Would Overwrite Them
+    Unreadable,
+}
+
+impl fmt::Display for LibraryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LibraryError::Json(error) => write!(f, "invalid library JSON: {}", error),
+            LibraryError::Store(error) => write!(f, "{}", error),
+            LibraryError::UnsupportedVersion(version) => write!(
+                f,
+                "library format version {} is newer than the supported version {}",
+                version, LIBRARY_FORMAT_VERSION
+            ),
+            LibraryError::EmptyName => write!(f, "a saved program needs a name"),
+            LibraryError::BuiltIn(name) => {
+                write!(f, "'{}' is a built-in program and cannot be changed", name)
+            }
+            LibraryError::UnknownProgram(name) => write!(f, "no program named '{}'", name),
+            LibraryError::Unreadable => write!(
+                f,
+                "the saved programs could not be read, so nothing is saved over them"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LibraryError {}
+
+impl From<StoreError> for LibraryError {
+    fn from(error: StoreError) -> LibraryError {
+        LibraryError::Store(error)
+    }
+}
+
+fn to_json(programs: &[Program]) -> Result<String, LibraryError> {
+    serde_json::to_string_pretty(&LibraryFile {
+        version: LIBRARY_FORMAT_VERSION,
+        programs: programs.to_vec(),
+    })
+    .map_err(LibraryError::Json)
+}
+
+fn from_json(source: &str) -> Result<Vec<Program>, LibraryError> {
+    let file: LibraryFile = serde_json::from_str(source).map_err(LibraryError::Json)?;
+    if file.version > LIBRARY_FORMAT_VERSION {
+        return Err(LibraryError::UnsupportedVersion(file.version));
+    }
+    Ok(file.programs)
+}
+
+fn load_programs(store: &dyn Store) -> Result<Vec<Program>, LibraryError> {
+    match store.load()? {
+        Some(source) => from_json(&source),
+        None => Ok(vec![]),
+    }
+}
+
+/**
+ * `programs.json` in the Data Directory, or the `oxidoro-programs` localStorage Key
+ */
+pub fn default_store() -> Box<dyn Store> {
+    store_util::default_store("programs.json")
+}
+
+/**
+ * Every Program the User Can Pick: the Built-In Ones First, Then the Ones They Saved
+ */
+pub struct ProgramLibrary {
+    built_in: Vec<Program>,
+    saved: Vec<Program>,
+    store: Box<dyn Store>,
+    unreadable: bool,
+}
+
+impl ProgramLibrary {
+    pub fn new(built_in: Vec<Program>, store: Box<dyn Store>) -> ProgramLibrary {
+        ProgramLibrary {
+            built_in,
+            saved: vec![],
+            store,
+            unreadable: false,
+        }
+    }
+
+    /**
+     * Replaces the Saved Programs With the Store's
+     *
+     * A saved program named like a built-in, e.g. one saved before that template was added,
+     * is kept as `<name> (saved)` so the next save does not drop it from the store. If the
+     * store cannot be read, saving and removing are refused until a later load succeeds.
+     */
+    pub fn load(&mut self) -> Result<(), LibraryError> {
+        let loaded = load_programs(self.store.as_ref());
+        self.unreadable = loaded.is_err();
+        let mut saved: Vec<Program> = vec![];
+        for mut program in loaded? {
+            while self.is_built_in(&program.name)
+                || saved.iter().any(|other| other.name == program.name)
+            {
+                program.name = format!("{} (saved)", program.name);
+            }
+            saved.push(program);
+        }
+        self.saved = saved;
+        Ok(())
+    }
+
+    pub fn programs(&self) -> impl Iterator<Item = &Program> {
+        self.built_in.iter().chain(self.saved.iter())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Program> {
+        self.programs().find(|program| program.name == name)
+    }
+
+    pub fn is_built_in(&self, name: &str) -> bool {
+        self.built_in.iter().any(|program| program.name == name)
+    }
+
+    /**
+     * Saves a Program Under Its Name, Replacing a Saved Program With the Same Name
+     */
+    pub fn save(&mut self, program: Program) -> Result<(), LibraryError> {
+        if program.name.trim().is_empty() {
+            return Err(LibraryError::EmptyName);
+        }
+        if self.is_built_in(&program.name) {
+            return Err(LibraryError::BuiltIn(program.name));
+        }
+        let mut saved = self.saved.clone();
+        match saved.iter_mut().find(|saved| saved.name == program.name) {
+            Some(saved) => *saved = program,
+            None => saved.push(program),
+        }
+        self.commit(saved)
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<(), LibraryError> {
+        if self.is_built_in(name) {
+            return Err(LibraryError::BuiltIn(name.to_string()));
+        }
+        let mut saved = self.saved.clone();
+        saved.retain(|program| program.name != name);
+        if saved.len() == self.saved.len() {
+            return Err(LibraryError::UnknownProgram(name.to_string()));
+        }
+        self.commit(saved)
+    }
+
+    /**
+     * Takes the New Saved Programs Only Once the Store Has Them, so a Failed Save Changes Nothing
+     */
+    fn commit(&mut self, saved: Vec<Program>) -> Result<(), LibraryError> {
+        if self.unreadable {
+            return Err(LibraryError::Unreadable);
+        }
+        self.store.save(&to_json(&saved)?)?;
+        self.saved = saved;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod library_util_tests {
+
+    use super::{from_json, to_json, LibraryError, ProgramLibrary};
+    use crate::program_util::Program;
+    use crate::store_util::{MemoryStore, Store, StoreError};
+    use crate::ProgramPhase::*;
+
+    fn program(name: &str, duration: usize) -> Program {
+        Program::new(
+            name,
+            vec![TimeFor {
+                duration,
+                label: None,
+            }],
+            vec![],
+        )
+    }
+
+    #[test]
+    fn save_and_reload() {
+        let store = MemoryStore::default();
+        let mut library =
+            ProgramLibrary::new(vec![program("Built In", 60)], Box::new(store.clone()));
+        library.save(program("Mine", 10)).unwrap();
+        library.save(program("Mine", 20)).unwrap();
+
+        let mut reloaded = ProgramLibrary::new(vec![program("Built In", 60)], Box::new(store));
+        reloaded.load().unwrap();
+        let names: Vec<&str> = reloaded
+            .programs()
+            .map(|program| program.name.as_str())
+            .collect();
+        assert_eq!(vec!["Built In", "Mine"], names);
+        assert_eq!(Some(&program("Mine", 20)), reloaded.get("Mine"));
+    }
+
+    #[test]
+    fn built_ins_are_read_only() {
+        let mut library = ProgramLibrary::new(
+            vec![program("Built In", 60)],
+            Box::new(MemoryStore::default()),
+        );
+        assert!(matches!(
+            library.save(program("Built In", 5)),
+            Err(LibraryError::BuiltIn(_))
+        ));
+        assert!(matches!(
+            library.remove("Built In"),
+            Err(LibraryError::BuiltIn(_))
+        ));
+        assert!(matches!(
+            library.remove("Missing"),
+            Err(LibraryError::UnknownProgram(_))
+        ));
+        assert!(matches!(
+            library.save(program(" ", 5)),
+            Err(LibraryError::EmptyName)
+        ));
+        assert_eq!(Some(&program("Built In", 60)), library.get("Built In"));
+    }
+
+    #[test]
+    fn shadowed_programs_are_kept() {
+        let store = MemoryStore::default();
+        store
+            .save(&to_json(&[program("Pomodoro", 10), program("Pomodoro (saved)", 20)]).unwrap())
+            .unwrap();
+        let mut library =
+            ProgramLibrary::new(vec![program("Pomodoro", 60)], Box::new(store.clone()));
+        library.load().unwrap();
+        assert_eq!(Some(&program("Pomodoro", 60)), library.get("Pomodoro"));
+        assert_eq!(
+            Some(&program("Pomodoro (saved)", 10)),
+            library.get("Pomodoro (saved)")
+        );
+        assert_eq!(
+            Some(&program("Pomodoro (saved) (saved)", 20)),
+            library.get("Pomodoro (saved) (saved)")
+        );
+
+        library.save(program("Other", 5)).unwrap();
+        let saved = from_json(&store.load().unwrap().unwrap()).unwrap();
+        assert_eq!(3, saved.len());
+    }
+
+    /**
+     * Fails Every Save, Like a Full Disk or a Full localStorage Quota
+     */
+    struct FailingStore;
+
+    impl Store for FailingStore {
+        fn load(&self) -> Result<Option<String>, StoreError> {
+            Ok(None)
+        }
+
+        fn save(&self, _contents: &str) -> Result<(), StoreError> {
+            Err(StoreError::Storage("full".into()))
+        }
+    }
+
+    #[test]
+    fn failed_save_changes_nothing() {
+        let mut library = ProgramLibrary::new(vec![], Box::new(FailingStore));
+        assert!(library.save(program("Mine", 10)).is_err());
+        assert_eq!(None, library.get("Mine"));
+    }
+
+    #[test]
+    fn unreadable_library_is_not_overwritten() {
+        let store = MemoryStore::default();
+        store.save("{ not json").unwrap();
+        let mut library = ProgramLibrary::new(vec![], Box::new(store.clone()));
+        assert!(matches!(library.load(), Err(LibraryError::Json(_))));
+        assert!(matches!(
+            library.save(program("Mine", 10)),
+            Err(LibraryError::Unreadable)
+        ));
+        assert!(matches!(
+            library.remove("Mine"),
+            Err(LibraryError::UnknownProgram(_))
+        ));
+        assert_eq!(Some("{ not json".to_string()), store.load().unwrap());
+    }
+
+    #[test]
+    fn remove_and_reload() {
+        let store = MemoryStore::default();
+        let mut library = ProgramLibrary::new(vec![], Box::new(store.clone()));
+        library.load().unwrap();
+        assert_eq!(0, library.programs().count());
+        library.save(program("Mine", 10)).unwrap();
+        library.remove("Mine").unwrap();
+        library.save(program("Other", 10)).unwrap();
+
+        let mut reloaded = ProgramLibrary::new(vec![], Box::new(store));
+        reloaded.load().unwrap();
+        assert_eq!(Some(&program("Other", 10)), reloaded.get("Other"));
+        assert_eq!(None, reloaded.get("Mine"));
+    }
+}
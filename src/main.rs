@@ -1,151 +1,582 @@
 slint::include_modules!();
 
-mod timer_util;
+pub mod audio_util;
+pub mod block_util;
+pub mod cli_util;
+pub mod clock_util;
+pub mod duration_util;
+pub mod editor_util;
+pub mod export_util;
+pub mod history_util;
+pub mod keymap_util;
+pub mod library_util;
+pub mod notification_util;
+pub mod parser_util;
+pub mod program_util;
+pub mod stats_util;
+pub mod store_util;
+pub mod template_util;
+pub mod timer_util;
 mod ui_util;
+pub mod validator_util;
 use std::{
+    cell::RefCell,
+    rc::Rc,
     sync::{Arc, Mutex},
-    time::Duration,
 };
 
+use block_util::ProgramBlock;
+use clock_util::{Clock, SystemClock, TickScheduler, TICK_INTERVAL};
+use editor_util::ProgramEditor;
+use history_util::HistoryRecorder;
+use library_util::ProgramLibrary;
+use notification_util::{NotificationError, NotificationSettings, Notifications};
+use program_util::Program;
 use timer_util::*;
 use ui_util::*;
 
+/**
+ * Feeds One Input to the Shared FSM, Returning Its Output Followed by Any Queued Effects and the New State
+ */
+fn run_input(model: &Arc<Mutex<TimerFSM>>, input: TimerInput) -> (Vec<TimerOutput>, TimerSnapshot) {
+    let mut model = model.lock().unwrap();
+    let mut outputs = vec![model.input(input)];
+    outputs.append(&mut model.take_effects());
+    (outputs, model.snapshot())
+}
+
+/**
+ * Runs One Input and Passes Every Output to the Scheduler, History, Notifications and UI, the
+ * Same Way for Buttons, Shortcuts and Ticks
+ */
+fn handle_input(
+    input: TimerInput,
+    model: &Arc<Mutex<TimerFSM>>,
+    scheduler: &Rc<RefCell<TickScheduler>>,
+    history: &Rc<RefCell<HistoryRecorder>>,
+    notifications: &Rc<RefCell<Notifications>>,
+    ui_handle: &slint::Weak<AppWindow>,
+) {
+    let (outputs, snapshot) = run_input(model, input);
+    for output in outputs {
+        scheduler.borrow_mut().observe(&output, SystemClock.now());
+        if let Err(error) = history.borrow_mut().record(&output) {
+            log_error(&error.to_string());
+        }
+        if let Err(error) = notifications.borrow_mut().handle(&output) {
+            log_error(&error.to_string());
+        }
+        data_to_ui(output, ui_handle);
+    }
+    snapshot_to_ui(&snapshot, ui_handle);
+}
+
+/**
+ * Applies One Change to the Program Being Edited and Redraws the Editor
+ */
+fn edit(
+    editor: &Rc<RefCell<ProgramEditor>>,
+    ui_handle: &slint::Weak<AppWindow>,
+    change: impl FnOnce(&mut ProgramEditor),
+) {
+    let mut editor = editor.borrow_mut();
+    change(&mut editor);
+    editor_to_ui(&editor, ui_handle);
+}
+
+/**
+ * Swaps the Shared FSM for a New Program's, Dropping Whatever Was Running so the Timer View Starts Over
+ */
+fn load_program(
+    fsm: TimerFSM,
+    name: &str,
+    model: &Arc<Mutex<TimerFSM>>,
+    scheduler: &Rc<RefCell<TickScheduler>>,
+    history: &Rc<RefCell<HistoryRecorder>>,
+    notifications: &Rc<RefCell<Notifications>>,
+    ui_handle: &slint::Weak<AppWindow>,
+) {
+    let ui = ui_handle.unwrap();
+    if let Err(error) = history.borrow_mut().set_program(name) {
+        log_error(&error.to_string());
+    }
+    notifications.borrow_mut().forget_run();
+    snapshot_to_ui(&fsm.snapshot(), ui_handle);
+    *model.lock().unwrap() = fsm;
+    scheduler.borrow_mut().stop();
+    reset_display(&ui);
+    ui.set_view(View::Timer);
+    ui.invoke_focus_shortcuts();
+}
+
 #[cfg_attr(target_arch = "wasm32", wasm_bindgen::prelude::wasm_bindgen(start))]
 pub fn main() /* -> Result<(), slint::PlatformError>*/
 {
     let ui = AppWindow::new().unwrap();
 
-    let program: Vec<ProgramPhase> = vec![
+    let blocks: Vec<ProgramBlock> = vec![
         // TAA
-        ProgramPhase::ReceiveInput,
-        ProgramPhase::TimeFor { duration: 10 },
-        ProgramPhase::OffsetVariable {
-            var_index: 0,
-            offset: -1,
-        },
-        ProgramPhase::Repeat {
-            to_phase: 0,
-            var_index: 0,
+        ProgramBlock::Loop {
+            count: 10,
+            body: vec![
+                ProgramPhase::ReceiveInput {
+                    label: Some("TAA".into()),
+                }
+                .into(),
+                ProgramPhase::TimeFor {
+                    duration: 10,
+                    label: Some("TAA".into()),
+                }
+                .into(),
+            ],
         },
         // SKtC
-        ProgramPhase::ReceiveInput,
-        ProgramPhase::TimeFor { duration: 20 },
-        ProgramPhase::OffsetVariable {
-            var_index: 1,
-            offset: -1,
-        },
-        ProgramPhase::Repeat {
-            to_phase: 4,
-            var_index: 1,
-        },
-        // LTR
-        ProgramPhase::ReceiveInput,
-        ProgramPhase::TimeFor { duration: 20 },
-        ProgramPhase::OffsetVariable {
-            var_index: 2,
-            offset: -1,
+        ProgramBlock::Loop {
+            count: 8,
+            body: vec![
+                ProgramPhase::ReceiveInput {
+                    label: Some("SKtC".into()),
+                }
+                .into(),
+                ProgramPhase::TimeFor {
+                    duration: 20,
+                    label: Some("SKtC".into()),
+                }
+                .into(),
+            ],
         },
-        ProgramPhase::Repeat {
-            to_phase: 8,
-            var_index: 2,
-        },
-        // SLB
-        ProgramPhase::ReceiveInput,
-        ProgramPhase::TimeFor { duration: 10 },
-        ProgramPhase::OffsetVariable {
-            var_index: 3,
-            offset: -1,
-        },
-        ProgramPhase::Repeat {
-            to_phase: 12,
-            var_index: 3,
-        },
-        // DB
-        ProgramPhase::ReceiveInput,
-        ProgramPhase::TimeFor { duration: 5 },
-        ProgramPhase::OffsetVariable {
-            var_index: 4,
-            offset: -1,
-        },
-        ProgramPhase::Repeat {
-            to_phase: 16,
-            var_index: 4,
-        },
-        // B
-        // Set 1
-        ProgramPhase::ReceiveInput,
-        ProgramPhase::OffsetVariable {
-            var_index: 5,
-            offset: -1,
-        },
-        ProgramPhase::Repeat {
-            to_phase: 10,
-            var_index: 5,
-        },
-        // Set 2
-        ProgramPhase::ReceiveInput,
-        ProgramPhase::OffsetVariable {
-            var_index: 6,
-            offset: -1,
-        },
-        ProgramPhase::Repeat {
-            to_phase: 10,
-            var_index: 6,
+        // B: Each Set 1 Goes Back Through LTR, SLB and DB, and Set 2 Repeats All of Set 1
+        ProgramBlock::Loop {
+            count: 10,
+            body: vec![
+                ProgramBlock::Loop {
+                    count: 10,
+                    body: vec![
+                        // LTR
+                        ProgramBlock::Loop {
+                            count: 8,
+                            body: vec![
+                                ProgramPhase::ReceiveInput {
+                                    label: Some("LTR".into()),
+                                }
+                                .into(),
+                                ProgramPhase::TimeFor {
+                                    duration: 20,
+                                    label: Some("LTR".into()),
+                                }
+                                .into(),
+                            ],
+                        },
+                        // SLB
+                        ProgramBlock::Loop {
+                            count: 10,
+                            body: vec![
+                                ProgramPhase::ReceiveInput {
+                                    label: Some("SLB".into()),
+                                }
+                                .into(),
+                                ProgramPhase::TimeFor {
+                                    duration: 10,
+                                    label: Some("SLB".into()),
+                                }
+                                .into(),
+                            ],
+                        },
+                        // DB
+                        ProgramBlock::Loop {
+                            count: 16,
+                            body: vec![
+                                ProgramPhase::ReceiveInput {
+                                    label: Some("DB".into()),
+                                }
+                                .into(),
+                                ProgramPhase::TimeFor {
+                                    duration: 5,
+                                    label: Some("DB".into()),
+                                }
+                                .into(),
+                            ],
+                        },
+                        ProgramPhase::ReceiveInput {
+                            label: Some("B Set 1".into()),
+                        }
+                        .into(),
+                    ],
+                },
+                ProgramPhase::ReceiveInput {
+                    label: Some("B Set 2".into()),
+                }
+                .into(),
+            ],
         },
     ];
-    let variables: Vec<i8> = vec![10, 8, 8, 10, 16, 10, 10];
-    let model: Arc<Mutex<TimerFSM>> =
-        Arc::new(Mutex::new(TimerFSM::new(program, variables.into())));
-    let timer: Arc<Mutex<slint::Timer>> = Arc::new(Mutex::new(slint::Timer::default()));
+    let program = Program::from_blocks("Exercises", &blocks).unwrap();
+    let model: Arc<Mutex<TimerFSM>> = Arc::new(Mutex::new(program.to_fsm().unwrap()));
+    let scheduler: Rc<RefCell<TickScheduler>> =
+        Rc::new(RefCell::new(TickScheduler::new(TICK_INTERVAL)));
+    let history: Rc<RefCell<HistoryRecorder>> = Rc::new(RefCell::new(HistoryRecorder::new(
+        history_util::default_store(),
+        &program.name,
+    )));
+    if let Err(error) = history.borrow_mut().load() {
+        log_error(&error.to_string());
+    }
+    let built_in: Vec<Program> = std::iter::once(program.clone())
+        .chain(template_util::built_in())
+        .collect();
+    let library: Rc<RefCell<ProgramLibrary>> = Rc::new(RefCell::new(ProgramLibrary::new(
+        built_in,
+        library_util::default_store(),
+    )));
+    if let Err(error) = library.borrow_mut().load() {
+        log_error(&error.to_string());
+    }
+    library_to_ui(&library.borrow(), &program.name, &ui.as_weak());
+    let settings = notification_util::load_settings().unwrap_or_else(|error| {
+        log_error(&error.to_string());
+        NotificationSettings::default()
+    });
+    ui.set_notify_phases(settings.phase_change);
+    ui.set_notify_input(settings.input_needed);
+    ui.set_notify_end(settings.program_end);
+    let notifications: Rc<RefCell<Notifications>> = Rc::new(RefCell::new(Notifications::new(
+        settings,
+        notification_util::default_notifier({
+            let ui_handle = ui.as_weak();
+            move || {
+                ui_handle.upgrade().is_some_and(|ui| {
+                    let window = ui.window();
+                    window.is_visible() && !window.is_minimized()
+                })
+            }
+        }),
+    )));
+    let editor: Rc<RefCell<ProgramEditor>> = Rc::new(RefCell::new(ProgramEditor::new(program)));
+    editor_to_ui(&editor.borrow(), &ui.as_weak());
+    snapshot_to_ui(&model.lock().unwrap().snapshot(), &ui.as_weak());
+
+    init_audio(&ui.as_weak());
 
     ui.on_play_sound({
-        || {
-            let _ = ui_util::play_sound(None); // TODO Error Handling
+        let ui_handle = ui.as_weak();
+        move || {
+            play_cue(audio_util::Sound::TimerDone, &ui_handle.unwrap());
+        }
+    });
+
+    // Clicking the Banner Counts as a User Gesture, Which Unblocks Browser Audio
+    ui.on_enable_sound({
+        let ui_handle = ui.as_weak();
+        move || {
+            let ui = ui_handle.unwrap();
+            ui.set_audio_error("".into());
+            play_cue(audio_util::Sound::TimerDone, &ui);
         }
     });
 
     ui.on_button_pressed({
         let ui_handle = ui.as_weak();
         let model_handle: Arc<Mutex<TimerFSM>> = model.clone();
+        let scheduler_handle = scheduler.clone();
+        let history_handle = history.clone();
+        let notifications_handle = notifications.clone();
         move |input| {
-            data_to_ui(model_handle.lock().unwrap().input(input), &ui_handle);
+            handle_input(
+                input,
+                &model_handle,
+                &scheduler_handle,
+                &history_handle,
+                &notifications_handle,
+                &ui_handle,
+            );
         }
     });
 
     ui.on_step({
         let ui_handle = ui.as_weak();
         let model_handle = model.clone();
+        let scheduler_handle = scheduler.clone();
+        let history_handle = history.clone();
+        let notifications_handle = notifications.clone();
         move || {
-            data_to_ui(
-                model_handle.lock().unwrap().input(TimerInput::Step),
+            handle_input(
+                TimerInput::Step,
+                &model_handle,
+                &scheduler_handle,
+                &history_handle,
+                &notifications_handle,
                 &ui_handle,
             );
         }
     });
 
-    ui.on_start_timer({
+    let keymap = keymap_util::load_keymap().unwrap_or_else(|error| {
+        log_error(&error.to_string());
+        keymap_util::Keymap::default()
+    });
+    // Shortcuts Go Through button_pressed, so They Behave Exactly Like the Buttons
+    ui.on_key_pressed({
+        let ui_handle = ui.as_weak();
+        let model_handle = model.clone();
+        move |text| {
+            let status = model_handle.lock().unwrap().status();
+            match keymap.input_for(&text, status) {
+                Some(input) => {
+                    ui_handle.unwrap().invoke_button_pressed(input);
+                    true
+                }
+                None => false,
+            }
+        }
+    });
+
+    // Permission Is Asked for on the Click That Opts In, Which Browsers Require
+    ui.on_notifications_changed({
         let ui_handle = ui.as_weak();
-        let timer_handle: Arc<Mutex<slint::Timer>> = timer.clone();
+        let notifications_handle = notifications.clone();
         move || {
             let ui = ui_handle.unwrap();
-            let timer = timer_handle.clone();
-            timer.lock().unwrap().start(
-                slint::TimerMode::Repeated,
-                Duration::new(1, 0),
-                move || {
-                    ui.invoke_step();
-                },
+            let settings = NotificationSettings {
+                phase_change: ui.get_notify_phases(),
+                input_needed: ui.get_notify_input(),
+                program_end: ui.get_notify_end(),
+            };
+            let done = {
+                let ui_handle = ui_handle.clone();
+                let notifications_handle = notifications_handle.clone();
+                move |result: Result<(), NotificationError>| {
+                    let ui = ui_handle.unwrap();
+                    match result.and_then(|()| notification_util::save_settings(&settings)) {
+                        Ok(()) => ui.set_notification_status("".into()),
+                        Err(error) => {
+                            // Without Permission the Old Choices Stay, so the Checkboxes Go Back to Them
+                            let kept = notifications_handle.borrow().settings();
+                            ui.set_notify_phases(kept.phase_change);
+                            ui.set_notify_input(kept.input_needed);
+                            ui.set_notify_end(kept.program_end);
+                            ui.set_notification_status(error.to_string().into());
+                        }
+                    }
+                }
+            };
+            Notifications::set_settings(&notifications_handle, settings, Box::new(done));
+        }
+    });
+
+    ui.on_editor_add({
+        let ui_handle = ui.as_weak();
+        let editor_handle = editor.clone();
+        move |kind| edit(&editor_handle, &ui_handle, |editor| editor.add(kind))
+    });
+
+    ui.on_editor_remove({
+        let ui_handle = ui.as_weak();
+        let editor_handle = editor.clone();
+        move |index| {
+            edit(&editor_handle, &ui_handle, |editor| {
+                editor.remove(index as usize)
+            })
+        }
+    });
+
+    ui.on_editor_move({
+        let ui_handle = ui.as_weak();
+        let editor_handle = editor.clone();
+        move |index, delta| {
+            edit(&editor_handle, &ui_handle, |editor| {
+                editor.move_phase(index as usize, delta)
+            })
+        }
+    });
+
+    ui.on_editor_set_value({
+        let ui_handle = ui.as_weak();
+        let editor_handle = editor.clone();
+        move |index, value| {
+            edit(&editor_handle, &ui_handle, |editor| {
+                editor.set_value(index as usize, value)
+            })
+        }
+    });
+
+    ui.on_editor_set_target({
+        let ui_handle = ui.as_weak();
+        let editor_handle = editor.clone();
+        move |index, target| {
+            edit(&editor_handle, &ui_handle, |editor| {
+                editor.set_target(index as usize, target)
+            })
+        }
+    });
+
+    ui.on_editor_rename({
+        let editor_handle = editor.clone();
+        move |name| editor_handle.borrow_mut().set_name(&name)
+    });
+
+    ui.on_editor_apply({
+        let ui_handle = ui.as_weak();
+        let model_handle = model.clone();
+        let scheduler_handle = scheduler.clone();
+        let editor_handle = editor.clone();
+        let history_handle = history.clone();
+        let notifications_handle = notifications.clone();
+        let library_handle = library.clone();
+        move || {
+            let editor = editor_handle.borrow();
+            match editor.build() {
+                Ok(fsm) => {
+                    let name = &editor.program().name;
+                    load_program(
+                        fsm,
+                        name,
+                        &model_handle,
+                        &scheduler_handle,
+                        &history_handle,
+                        &notifications_handle,
+                        &ui_handle,
+                    );
+                    library_to_ui(&library_handle.borrow(), name, &ui_handle);
+                }
+                Err(error) => ui_handle
+                    .unwrap()
+                    .set_editor_status(error.to_string().into()),
+            }
+        }
+    });
+
+    // Only Valid Programs Go in the Library, so Anything Picked From It Can Run
+    ui.on_editor_save({
+        let ui_handle = ui.as_weak();
+        let editor_handle = editor.clone();
+        let history_handle = history.clone();
+        let library_handle = library.clone();
+        move || {
+            let ui = ui_handle.unwrap();
+            let editor = editor_handle.borrow();
+            let saved = match editor.build() {
+                Ok(_) => library_handle
+                    .borrow_mut()
+                    .save(editor.program().clone())
+                    .map_err(|error| error.to_string()),
+                Err(error) => Err(error.to_string()),
+            };
+            match saved {
+                Ok(()) => {
+                    ui.set_editor_status(format!("Saved '{}'", editor.program().name).into());
+                    library_to_ui(
+                        &library_handle.borrow(),
+                        history_handle.borrow().program(),
+                        &ui_handle,
+                    );
+                }
+                Err(error) => ui.set_editor_status(error.into()),
+            }
+        }
+    });
+
+    ui.on_library_select({
+        let ui_handle = ui.as_weak();
+        let model_handle = model.clone();
+        let scheduler_handle = scheduler.clone();
+        let editor_handle = editor.clone();
+        let history_handle = history.clone();
+        let notifications_handle = notifications.clone();
+        let library_handle = library.clone();
+        move |name| {
+            let ui = ui_handle.unwrap();
+            let library = library_handle.borrow();
+            let Some(program) = library.get(&name) else {
+                ui.set_library_status(format!("no program named '{}'", name).into());
+                return;
+            };
+            match program.to_fsm() {
+                Ok(fsm) => {
+                    load_program(
+                        fsm,
+                        &name,
+                        &model_handle,
+                        &scheduler_handle,
+                        &history_handle,
+                        &notifications_handle,
+                        &ui_handle,
+                    );
+                    *editor_handle.borrow_mut() = ProgramEditor::new(program.clone());
+                    editor_to_ui(&editor_handle.borrow(), &ui_handle);
+                    ui.set_library_status("".into());
+                    library_to_ui(&library, &name, &ui_handle);
+                }
+                Err(error) => ui.set_library_status(error.to_string().into()),
+            }
+        }
+    });
+
+    ui.on_library_remove({
+        let ui_handle = ui.as_weak();
+        let history_handle = history.clone();
+        let library_handle = library.clone();
+        move |name| {
+            let ui = ui_handle.unwrap();
+            match library_handle.borrow_mut().remove(&name) {
+                Ok(()) => ui.set_library_status(format!("Deleted '{}'", name).into()),
+                Err(error) => ui.set_library_status(error.to_string().into()),
+            }
+            library_to_ui(
+                &library_handle.borrow(),
+                history_handle.borrow().program(),
+                &ui_handle,
             );
         }
     });
 
-    ui.on_stop_timer({
-        let timer_handle: Arc<Mutex<slint::Timer>> = timer.clone();
+    ui.on_show_stats({
+        let ui_handle = ui.as_weak();
+        let history_handle = history.clone();
+        move || {
+            let today = stats_util::local_day(history_util::unix_millis());
+            let stats = stats_util::stats(
+                history_handle.borrow().history(),
+                today,
+                stats_util::utc_offset,
+            );
+            stats_to_ui(&stats, today, &ui_handle);
+            let ui = ui_handle.unwrap();
+            ui.set_export_status("".into());
+            ui.set_view(View::Stats);
+        }
+    });
+
+    ui.on_export_history({
+        let ui_handle = ui.as_weak();
+        let history_handle = history.clone();
+        move |format| {
+            let status = match export_util::save_export(history_handle.borrow().history(), format) {
+                Ok(destination) => format!("Exported to {}", destination),
+                Err(error) => {
+                    log_error(&error.to_string());
+                    format!("Export failed: {}", error)
+                }
+            };
+            ui_handle.unwrap().set_export_status(status.into());
+        }
+    });
+
+    // The Platform Timer Only Wakes Us Up, the Scheduler Decides if a Step Is Due
+    let timer = slint::Timer::default();
+    timer.start(slint::TimerMode::Repeated, TICK_INTERVAL, {
+        let ui_handle = ui.as_weak();
+        let scheduler_handle = scheduler.clone();
         move || {
-            let timer = timer_handle.clone();
-            timer.lock().unwrap().stop();
+            if scheduler_handle.borrow_mut().due(SystemClock.now()) {
+                ui_handle.unwrap().invoke_step();
+            }
         }
     });
 
     ui.run().unwrap();
+    // Closing the Window Mid-Run Counts as Stopping It, Like Quitting the CLI
+    let closed = history.borrow_mut().close();
+    if let Err(error) = closed {
+        log_error(&error.to_string());
+    }
 }
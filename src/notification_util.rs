@@ -0,0 +1,558 @@
+use crate::duration_util::seconds_to_h_m_s_display_string;
+use crate::store_util::{self, Store, StoreError};
+use crate::{ProgramPhase, TimerOutput};
+
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+/**
+ * Which Events the User Opted in to Being Notified About; All Off Until They Choose
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationSettings {
+    /// A New Timer Started
+    pub phase_change: bool,
+    /// The Program Is Waiting for the User
+    pub input_needed: bool,
+    /// The Program Finished or Was Stopped
+    pub program_end: bool,
+}
+
+impl NotificationSettings {
+    pub fn any(&self) -> bool {
+        self.phase_change || self.input_needed || self.program_end
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    pub title: String,
+    pub body: String,
+}
+
+#[derive(Debug)]
+pub enum NotificationError {
+    Unsupported,
+    Denied,
+    Failed(String),
+    Json(serde_json::Error),
+    Store(StoreError),
+}
+
+impl fmt::Display for NotificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NotificationError::Unsupported => {
+                write!(f, "notifications are not supported on this platform")
+            }
+            NotificationError::Denied => write!(f, "notifications were not allowed"),
+            NotificationError::Failed(reason) => {
+                write!(f, "could not show notification: {}", reason)
+            }
+            NotificationError::Json(error) => {
+                write!(f, "invalid notification settings JSON: {}", error)
+            }
+            NotificationError::Store(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for NotificationError {}
+
+impl From<StoreError> for NotificationError {
+    fn from(error: StoreError) -> NotificationError {
+        NotificationError::Store(error)
+    }
+}
+
+/**
+ * The Notification for One FSM Output, if It Is an Event the User Opted in to
+ */
+pub fn notification_for(
+    output: &TimerOutput,
+    settings: &NotificationSettings,
+) -> Option<Notification> {
+    let notification = |title: &str, body: String| {
+        Some(Notification {
+            title: title.to_string(),
+            body,
+        })
+    };
+    match output {
+        TimerOutput::PhaseChange { next_phase, .. } => match next_phase {
+            ProgramPhase::TimeFor { duration, label } if settings.phase_change => notification(
+                label.as_deref().unwrap_or("Timer started"),
+                format!(
+                    "{} on the clock",
+                    seconds_to_h_m_s_display_string(*duration)
+                ),
+            ),
+            ProgramPhase::ReceiveInput { label } if settings.input_needed => notification(
+                label.as_deref().unwrap_or("Input needed"),
+                "Waiting for you to continue".to_string(),
+            ),
+            ProgramPhase::EndProgram if settings.program_end => {
+                notification("Program done", "Every phase is finished".to_string())
+            }
+            _ => None,
+        },
+        TimerOutput::ProgramStopped { .. } if settings.program_end => notification(
+            "Program stopped",
+            "The program was stopped early".to_string(),
+        ),
+        _ => None,
+    }
+}
+
+/**
+ * Told Whether Permission Was Granted, Which Browsers Only Decide Once the User Answers
+ */
+pub type PermissionHandler = Box<dyn FnOnce(Result<(), NotificationError>)>;
+
+/**
+ * Shows Notifications Outside the Window, Picked per Target by `default_notifier`
+ */
+pub trait Notifier {
+    /**
+     * Asks for Permission Where the Platform Needs It, Called When the User Opts In
+     */
+    fn request_permission(&self, done: PermissionHandler) {
+        done(Ok(()))
+    }
+
+    fn notify(&self, notification: &Notification) -> Result<(), NotificationError>;
+}
+
+/**
+ * Freedesktop Notifications Through `notify-send`; Skipped While the Window Is on Screen, Like the Web Path
+ */
+#[cfg(all(not(target_arch = "wasm32"), target_os = "linux"))]
+pub struct DesktopNotifier {
+    window_visible: Box<dyn Fn() -> bool>,
+}
+
+#[cfg(all(not(target_arch = "wasm32"), target_os = "linux"))]
+impl Notifier for DesktopNotifier {
+    fn notify(&self, notification: &Notification) -> Result<(), NotificationError> {
+        if (self.window_visible)() {
+            return Ok(());
+        }
+        let mut child = std::process::Command::new("notify-send")
+            .arg("--app-name=Oxidoro")
+            .arg(&notification.title)
+            .arg(&notification.body)
+            .spawn()
+            .map_err(|error| NotificationError::Failed(format!("notify-send: {}", error)))?;
+        // Reap It Off the UI Thread so a Slow Notification Daemon Does Not Stall the Timer
+        std::thread::spawn(move || child.wait());
+        Ok(())
+    }
+}
+
+/**
+ * The Web Notifications API; Skipped While the Tab Is Visible, Where the Page Already Shows It
+ */
+#[cfg(target_arch = "wasm32")]
+#[derive(Debug, Clone, Default)]
+pub struct WebNotifier;
+
+#[cfg(target_arch = "wasm32")]
+impl Notifier for WebNotifier {
+    fn request_permission(&self, done: PermissionHandler) {
+        match web_sys::Notification::permission() {
+            web_sys::NotificationPermission::Granted => done(Ok(())),
+            web_sys::NotificationPermission::Denied => done(Err(NotificationError::Denied)),
+            _ => match web_sys::Notification::request_permission() {
+                // The Answer Only Arrives Once the User Clicks Allow or Block
+                Ok(promise) => wasm_bindgen_futures::spawn_local(async move {
+                    let answer = wasm_bindgen_futures::JsFuture::from(promise).await;
+                    done(match answer {
+                        Ok(permission) if permission.as_string().as_deref() == Some("granted") => {
+                            Ok(())
+                        }
+                        Ok(_) => Err(NotificationError::Denied),
+                        Err(err) => Err(NotificationError::Failed(format!("{:?}", err))),
+                    })
+                }),
+                Err(err) => done(Err(NotificationError::Failed(format!("{:?}", err)))),
+            },
+        }
+    }
+
+    fn notify(&self, notification: &Notification) -> Result<(), NotificationError> {
+        let visible = web_sys::window()
+            .and_then(|window| window.document())
+            .is_some_and(|document| !document.hidden());
+        if visible {
+            return Ok(());
+        }
+        if web_sys::Notification::permission() != web_sys::NotificationPermission::Granted {
+            return Err(NotificationError::Denied);
+        }
+        let mut options = web_sys::NotificationOptions::new();
+        options.body(&notification.body);
+        web_sys::Notification::new_with_options(&notification.title, &options)
+            .map(|_| ())
+            .map_err(|err| NotificationError::Failed(format!("{:?}", err)))
+    }
+}
+
+/**
+ * For Desktops Without Freedesktop Notifications
+ */
+#[derive(Debug, Clone, Default)]
+pub struct UnsupportedNotifier;
+
+impl Notifier for UnsupportedNotifier {
+    fn request_permission(&self, done: PermissionHandler) {
+        done(Err(NotificationError::Unsupported))
+    }
+
+    fn notify(&self, _notification: &Notification) -> Result<(), NotificationError> {
+        Err(NotificationError::Unsupported)
+    }
+}
+
+/**
+ * Keeps Every Notification Instead of Showing It; Clones Share the Same List
+ */
+#[derive(Debug, Clone, Default)]
+pub struct RecordingNotifier {
+    sent: Rc<RefCell<Vec<Notification>>>,
+}
+
+impl RecordingNotifier {
+    pub fn sent(&self) -> Vec<Notification> {
+        self.sent.borrow().clone()
+    }
+}
+
+impl Notifier for RecordingNotifier {
+    fn notify(&self, notification: &Notification) -> Result<(), NotificationError> {
+        self.sent.borrow_mut().push(notification.clone());
+        Ok(())
+    }
+}
+
+/**
+ * The Notifier for This Platform; `window_visible` Tells the Desktop One When the App Already Shows the Event
+ */
+#[cfg(all(not(target_arch = "wasm32"), target_os = "linux"))]
+pub fn default_notifier(window_visible: impl Fn() -> bool + 'static) -> Box<dyn Notifier> {
+    Box::new(DesktopNotifier {
+        window_visible: Box::new(window_visible),
+    })
+}
+
+#[cfg(all(not(target_arch = "wasm32"), not(target_os = "linux")))]
+pub fn default_notifier(_window_visible: impl Fn() -> bool + 'static) -> Box<dyn Notifier> {
+    Box::new(UnsupportedNotifier)
+}
+
+// The Browser Knows Whether the Tab Is Hidden, so the Window Is Not Asked
+#[cfg(target_arch = "wasm32")]
+pub fn default_notifier(_window_visible: impl Fn() -> bool + 'static) -> Box<dyn Notifier> {
+    Box::new(WebNotifier)
+}
+
+/**
+ * `notifications.json` in the Data Directory, or the `oxidoro-notifications` localStorage Key
+ */
+fn settings_store() -> Box<dyn Store> {
+    store_util::default_store("notifications.json")
+}
+
+pub fn load_settings() -> Result<NotificationSettings, NotificationError> {
+    match settings_store().load()? {
+        Some(source) => serde_json::from_str(&source).map_err(NotificationError::Json),
+        None => Ok(NotificationSettings::default()),
+    }
+}
+
+pub fn save_settings(settings: &NotificationSettings) -> Result<(), NotificationError> {
+    let json = serde_json::to_string_pretty(settings).map_err(NotificationError::Json)?;
+    Ok(settings_store().save(&json)?)
+}
+
+/**
+ * The User's Notification Choices Together With the Notifier That Shows Them
+ */
+pub struct Notifications {
+    settings: NotificationSettings,
+    notifier: Rc<dyn Notifier>,
+    // Stop Also Comes Through While Idle, Where There Is No Run to Report as Stopped
+    running: bool,
+}
+
+impl Notifications {
+    pub fn new(settings: NotificationSettings, notifier: Box<dyn Notifier>) -> Notifications {
+        Notifications {
+            settings,
+            notifier: notifier.into(),
+            running: false,
+        }
+    }
+
+    pub fn settings(&self) -> NotificationSettings {
+        self.settings
+    }
+
+    /**
+     * Takes the New Choices Once Permission Is Granted, Asking First if Anything Is Now Turned
+     * On; Keeps the Old Ones if Permission Fails
+     *
+     * Browsers answer later, so the result goes to `done` rather than being returned, and
+     * `notifications` is not borrowed while the notifier asks.
+     */
+    pub fn set_settings(
+        notifications: &Rc<RefCell<Notifications>>,
+        settings: NotificationSettings,
+        done: PermissionHandler,
+    ) {
+        if !settings.any() {
+            notifications.borrow_mut().settings = settings;
+            return done(Ok(()));
+        }
+        let notifier = notifications.borrow().notifier.clone();
+        let notifications = notifications.clone();
+        notifier.request_permission(Box::new(move |result| {
+            if result.is_ok() {
+                notifications.borrow_mut().settings = settings;
+            }
+            done(result)
+        }));
+    }
+
+    /**
+     * Drops the Open Run Along With the FSM That Ran It, so a Stop While Idle Afterwards Stays Quiet
+     */
+    pub fn forget_run(&mut self) {
+        self.running = false;
+    }
+
+    pub fn handle(&mut self, output: &TimerOutput) -> Result<(), NotificationError> {
+        let was_running = self.running;
+        match output {
+            TimerOutput::PhaseChange { next_phase, .. } => {
+                self.running = *next_phase != ProgramPhase::EndProgram;
+            }
+            TimerOutput::ProgramStopped { .. } => {
+                self.running = false;
+                if !was_running {
+                    return Ok(());
+                }
+            }
+            _ => {}
+        }
+        match notification_for(output, &self.settings) {
+            Some(notification) => self.notifier.notify(&notification),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod notification_util_tests {
+
+    use super::{
+        Notification, NotificationError, NotificationSettings, Notifications, Notifier,
+        PermissionHandler, RecordingNotifier, UnsupportedNotifier,
+    };
+    use crate::ProgramPhase::*;
+    use crate::TimerOutput;
+    use crate::TimerOutput::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn phase_change(next_phase: crate::ProgramPhase) -> TimerOutput {
+        PhaseChange {
+            prev_phase: BeginProgram,
+            next_phase,
+            phase_completed: true,
+        }
+    }
+
+    fn example_outputs() -> Vec<TimerOutput> {
+        vec![
+            // Stopping Before Anything Runs Is Not Worth a Notification
+            ProgramStopped {
+                program_phase: BeginProgram,
+            },
+            phase_change(TimeFor {
+                duration: 1500,
+                label: Some("Focus".into()),
+            }),
+            phase_change(ReceiveInput { label: None }),
+            TimerPaused,
+            phase_change(EndProgram),
+            phase_change(ReceiveInput { label: None }),
+            ProgramStopped {
+                program_phase: ReceiveInput { label: None },
+            },
+        ]
+    }
+
+    /**
+     * Sets the Choices Through a Notifier That Answers Right Away, Returning Its Answer
+     */
+    fn set_now(
+        notifications: &Rc<RefCell<Notifications>>,
+        settings: NotificationSettings,
+    ) -> Result<(), NotificationError> {
+        let answer = Rc::new(RefCell::new(None));
+        Notifications::set_settings(notifications, settings, {
+            let answer = answer.clone();
+            Box::new(move |result| *answer.borrow_mut() = Some(result))
+        });
+        answer.take().expect("the notifier answered later")
+    }
+
+    #[test]
+    fn nothing_without_opt_in() {
+        let notifier = RecordingNotifier::default();
+        let mut notifications =
+            Notifications::new(NotificationSettings::default(), Box::new(notifier.clone()));
+        for output in example_outputs() {
+            notifications.handle(&output).unwrap();
+        }
+        assert!(notifier.sent().is_empty());
+    }
+
+    #[test]
+    fn per_event_opt_in() {
+        let notifier = RecordingNotifier::default();
+        let notifications = Rc::new(RefCell::new(Notifications::new(
+            NotificationSettings::default(),
+            Box::new(notifier.clone()),
+        )));
+        set_now(
+            &notifications,
+            NotificationSettings {
+                phase_change: true,
+                input_needed: false,
+                program_end: true,
+            },
+        )
+        .unwrap();
+        for output in example_outputs() {
+            notifications.borrow_mut().handle(&output).unwrap();
+        }
+        assert_eq!(
+            vec![
+                Notification {
+                    title: "Focus".into(),
+                    body: "00:25:00 on the clock".into(),
+                },
+                Notification {
+                    title: "Program done".into(),
+                    body: "Every phase is finished".into(),
+                },
+                Notification {
+                    title: "Program stopped".into(),
+                    body: "The program was stopped early".into(),
+                },
+            ],
+            notifier.sent()
+        );
+    }
+
+    #[test]
+    fn stop_after_forgotten_run() {
+        let notifier = RecordingNotifier::default();
+        let mut notifications = Notifications::new(
+            NotificationSettings {
+                program_end: true,
+                ..NotificationSettings::default()
+            },
+            Box::new(notifier.clone()),
+        );
+        let stopped = ProgramStopped {
+            program_phase: BeginProgram,
+        };
+        notifications
+            .handle(&phase_change(ReceiveInput { label: None }))
+            .unwrap();
+        notifications.forget_run();
+        notifications.handle(&stopped).unwrap();
+        assert!(notifier.sent().is_empty());
+    }
+
+    #[test]
+    fn unsupported_platform() {
+        let notifications = Rc::new(RefCell::new(Notifications::new(
+            NotificationSettings::default(),
+            Box::new(UnsupportedNotifier),
+        )));
+        // Turning Everything Off Never Needs the Platform
+        assert!(set_now(&notifications, NotificationSettings::default()).is_ok());
+        assert!(matches!(
+            set_now(
+                &notifications,
+                NotificationSettings {
+                    input_needed: true,
+                    ..NotificationSettings::default()
+                }
+            ),
+            Err(NotificationError::Unsupported)
+        ));
+        assert_eq!(
+            NotificationSettings::default(),
+            notifications.borrow().settings()
+        );
+    }
+
+    /**
+     * Holds On to the Permission Request Like a Browser Waiting for the User's Click
+     */
+    #[derive(Clone, Default)]
+    struct AskingNotifier {
+        pending: Rc<RefCell<Option<PermissionHandler>>>,
+    }
+
+    impl Notifier for AskingNotifier {
+        fn request_permission(&self, done: PermissionHandler) {
+            *self.pending.borrow_mut() = Some(done);
+        }
+
+        fn notify(&self, _notification: &Notification) -> Result<(), NotificationError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn settings_wait_for_the_answer() {
+        let notifier = AskingNotifier::default();
+        let notifications = Rc::new(RefCell::new(Notifications::new(
+            NotificationSettings::default(),
+            Box::new(notifier.clone()),
+        )));
+        let opt_in = NotificationSettings {
+            program_end: true,
+            ..NotificationSettings::default()
+        };
+        let answers = Rc::new(RefCell::new(vec![]));
+        for answer in [Err(NotificationError::Denied), Ok(())] {
+            Notifications::set_settings(&notifications, opt_in, {
+                let answers = answers.clone();
+                Box::new(move |result| answers.borrow_mut().push(result.is_ok()))
+            });
+            assert_eq!(
+                NotificationSettings::default(),
+                notifications.borrow().settings()
+            );
+            let done = notifier.pending.take().unwrap();
+            let granted = answer.is_ok();
+            done(answer);
+            let expected = if granted {
+                opt_in
+            } else {
+                NotificationSettings::default()
+            };
+            assert_eq!(expected, notifications.borrow().settings());
+        }
+        assert_eq!(vec![false, true], *answers.borrow());
+    }
+}
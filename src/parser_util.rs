@@ -0,0 +1,668 @@
+use crate::audio_util::Sound;
+use crate::block_util::{compile, ProgramBlock};
+use crate::ProgramPhase;
+
+use std::fmt;
+
+/**
+ * A Program Compiled from the Text Format, Ready to be Handed to TimerFSM::new
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedProgram {
+    pub phases: Vec<ProgramPhase>,
+    pub variables: Vec<i8>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Word(String),
+    Number(usize),
+    Duration(usize),
+    Text(String),
+    OpenBrace,
+    CloseBrace,
+    Semicolon,
+    End,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Token {
+    kind: TokenKind,
+    line: usize,
+    column: usize,
+}
+
+fn describe(kind: &TokenKind) -> String {
+    match kind {
+        TokenKind::Word(word) => format!("'{}'", word),
+        TokenKind::Number(number) => format!("'{}'", number),
+        TokenKind::Duration(_) => "a duration".into(),
+        TokenKind::Text(_) => "a string".into(),
+        TokenKind::OpenBrace => "'{'".into(),
+        TokenKind::CloseBrace => "'}'".into(),
+        TokenKind::Semicolon => "';'".into(),
+        TokenKind::End => "end of input".into(),
+    }
+}
+
+fn unit_seconds(unit: char) -> Option<usize> {
+    match unit {
+        'h' => Some(3600),
+        'm' => Some(60),
+        's' => Some(1),
+        _ => None,
+    }
+}
+
+/**
+ * Splits the Source into Tokens, Skipping Whitespace and Comments (`#` or `//` to End of Line)
+ */
+fn tokenize(source: &str) -> Result<Vec<Token>, ParseError> {
+    let mut tokens: Vec<Token> = vec![];
+    let mut chars = source.chars().peekable();
+    let mut line: usize = 1;
+    let mut column: usize = 1;
+
+    while let Some(&c) = chars.peek() {
+        let (start_line, start_column) = (line, column);
+        let error = |message: String| ParseError {
+            line: start_line,
+            column: start_column,
+            message,
+        };
+        if c == '\n' {
+            chars.next();
+            line += 1;
+            column = 1;
+            continue;
+        }
+        if c.is_whitespace() {
+            chars.next();
+            column += 1;
+            continue;
+        }
+        if c == '#' || (c == '/' && source_continues_with(&chars, "//")) {
+            while let Some(&c) = chars.peek() {
+                if c == '\n' {
+                    break;
+                }
+                chars.next();
+                column += 1;
+            }
+            continue;
+        }
+        let kind = match c {
+            '{' | '}' | ';' => {
+                chars.next();
+                column += 1;
+                match c {
+                    '{' => TokenKind::OpenBrace,
+                    '}' => TokenKind::CloseBrace,
+                    _ => TokenKind::Semicolon,
+                }
+            }
+            '0'..='9' => {
+                let mut digits = String::new();
+                while let Some(&c) = chars.peek().filter(|c| c.is_ascii_digit()) {
+                    digits.push(c);
+                    chars.next();
+                    column += 1;
+                }
+                let number: usize = digits
+                    .parse()
+                    .map_err(|_| error(format!("number '{}' is too large", digits)))?;
+                match chars.peek().copied().and_then(unit_seconds) {
+                    Some(multiplier) => {
+                        chars.next();
+                        column += 1;
+                        TokenKind::Duration(
+                            number.checked_mul(multiplier).ok_or_else(|| {
+                                error(format!("duration '{}' is too large", digits))
+                            })?,
+                        )
+                    }
+                    None => TokenKind::Number(number),
+                }
+            }
+            '"' => {
+                chars.next();
+                column += 1;
+                let mut text = String::new();
+                loop {
+                    let c = match chars.next() {
+                        Some('\n') | None => {
+                            return Err(error("unterminated string".into()));
+                        }
+                        Some(c) => c,
+                    };
+                    column += 1;
+                    match c {
+                        '"' => break,
+                        '\\' => {
+                            let escaped = chars.next();
+                            column += 1;
+                            match escaped {
+                                Some('"') => text.push('"'),
+                                Some('\\') => text.push('\\'),
+                                Some('n') => text.push('\n'),
+                                _ => return Err(error("invalid escape in string".into())),
+                            }
+                        }
+                        c => text.push(c),
+                    }
+                }
+                TokenKind::Text(text)
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek().filter(|c| c.is_alphanumeric() || **c == '_') {
+                    word.push(c);
+                    chars.next();
+                    column += 1;
+                }
+                TokenKind::Word(word)
+            }
+            _ => return Err(error(format!("unexpected character '{}'", c))),
+        };
+        if let Some(&c) = chars.peek() {
+            if matches!(kind, TokenKind::Number(_) | TokenKind::Duration(_))
+                && (c.is_alphanumeric() || c == '_')
+            {
+                return Err(ParseError {
+                    line,
+                    column,
+                    message: format!("unexpected '{}' after number, expected h, m or s", c),
+                });
+            }
+        }
+        tokens.push(Token {
+            kind,
+            line: start_line,
+            column: start_column,
+        });
+    }
+    tokens.push(Token {
+        kind: TokenKind::End,
+        line,
+        column,
+    });
+    Ok(tokens)
+}
+
+fn source_continues_with(chars: &std::iter::Peekable<std::str::Chars<'_>>, prefix: &str) -> bool {
+    chars.clone().take(prefix.len()).eq(prefix.chars())
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.position]
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.tokens[self.position].clone();
+        if token.kind != TokenKind::End {
+            self.position += 1;
+        }
+        token
+    }
+
+    fn error_at(token: &Token, message: String) -> ParseError {
+        ParseError {
+            line: token.line,
+            column: token.column,
+            message,
+        }
+    }
+
+    fn expect(&mut self, kind: TokenKind) -> Result<Token, ParseError> {
+        let token = self.advance();
+        if token.kind == kind {
+            Ok(token)
+        } else {
+            Err(Parser::error_at(
+                &token,
+                format!(
+                    "expected {}, found {}",
+                    describe(&kind),
+                    describe(&token.kind)
+                ),
+            ))
+        }
+    }
+
+    /**
+     * Parses Statements Until the Closing Token
+     */
+    fn statements(&mut self, closing: TokenKind) -> Result<Vec<ProgramBlock>, ParseError> {
+        let mut blocks: Vec<ProgramBlock> = vec![];
+        while self.peek().kind != closing {
+            blocks.push(self.statement()?);
+        }
+        Ok(blocks)
+    }
+
+    fn statement(&mut self) -> Result<ProgramBlock, ParseError> {
+        let token = self.advance();
+        let phase = match &token.kind {
+            TokenKind::Word(word) if word == "input" => {
+                let label = self.label();
+                self.expect(TokenKind::Semicolon)?;
+                ProgramPhase::ReceiveInput { label }
+            }
+            TokenKind::Word(word) if word == "time" => {
+                let duration = self.duration(&token)?;
+                let label = self.label();
+                self.expect(TokenKind::Semicolon)?;
+                ProgramPhase::TimeFor { duration, label }
+            }
+            TokenKind::Word(word) if word == "text" => {
+                let text = match self.label() {
+                    Some(text) => text,
+                    None => {
+                        let found = self.peek().clone();
+                        return Err(Parser::error_at(
+                            &found,
+                            format!("expected a string, found {}", describe(&found.kind)),
+                        ));
+                    }
+                };
+                self.expect(TokenKind::Semicolon)?;
+                ProgramPhase::DisplayText { text }
+            }
+            TokenKind::Word(word) if word == "repeat" => {
+                return self.repeat();
+            }
+            TokenKind::Word(word) if word == "sound" => {
+                let name = self.advance();
+                let sound = match &name.kind {
+                    TokenKind::Word(word) => Sound::from_name(word).ok_or_else(|| {
+                        Parser::error_at(&name, format!("unknown sound '{}'", word))
+                    })?,
+                    kind => {
+                        return Err(Parser::error_at(
+                            &name,
+                            format!("expected a sound name, found {}", describe(kind)),
+                        ))
+                    }
+                };
+                self.expect(TokenKind::Semicolon)?;
+                ProgramPhase::PlaySound { sound }
+            }
+            TokenKind::Word(word) => {
+                return Err(Parser::error_at(
+                    &token,
+                    format!(
+                        "unknown statement '{}', expected input, time, text, sound or repeat",
+                        word
+                    ),
+                ));
+            }
+            kind => {
+                return Err(Parser::error_at(
+                    &token,
+                    format!("expected a statement, found {}", describe(kind)),
+                ));
+            }
+        };
+        Ok(phase.into())
+    }
+
+    /**
+     * An Optional Quoted Label Following a Statement's Arguments
+     */
+    fn label(&mut self) -> Option<String> {
+        match &self.peek().kind {
+            TokenKind::Text(text) => {
+                let text = text.clone();
+                self.advance();
+                Some(text)
+            }
+            _ => None,
+        }
+    }
+
+    /**
+     * One or More Duration Parts, e.g. `1h 30m`; a Bare Number is Seconds
+     */
+    fn duration(&mut self, keyword: &Token) -> Result<usize, ParseError> {
+        let mut total: Option<usize> = None;
+        while let TokenKind::Duration(seconds) | TokenKind::Number(seconds) = self.peek().kind {
+            let token = self.advance();
+            total = Some(
+                total
+                    .unwrap_or(0)
+                    .checked_add(seconds)
+                    .ok_or_else(|| Parser::error_at(&token, "duration is too large".into()))?,
+            );
+        }
+        match total {
+            Some(0) => Err(Parser::error_at(
+                keyword,
+                "duration must be greater than zero".into(),
+            )),
+            Some(total) => Ok(total),
+            None => {
+                let token = self.peek().clone();
+                Err(Parser::error_at(
+                    &token,
+                    format!("expected a duration, found {}", describe(&token.kind)),
+                ))
+            }
+        }
+    }
+
+    /**
+     * `repeat N { ... }` Becomes a Loop Block
+     */
+    fn repeat(&mut self) -> Result<ProgramBlock, ParseError> {
+        let count_token = self.advance();
+        let count: i8 = match count_token.kind {
+            TokenKind::Number(count) if (1..=i8::MAX as usize).contains(&count) => count as i8,
+            TokenKind::Number(count) => {
+                return Err(Parser::error_at(
+                    &count_token,
+                    format!(
+                        "repeat count must be between 1 and {}, found {}",
+                        i8::MAX,
+                        count
+                    ),
+                ))
+            }
+            ref kind => {
+                return Err(Parser::error_at(
+                    &count_token,
+                    format!("expected a repeat count, found {}", describe(kind)),
+                ))
+            }
+        };
+        let open = self.expect(TokenKind::OpenBrace)?;
+        let body = self.statements(TokenKind::CloseBrace)?;
+        self.expect(TokenKind::CloseBrace)?;
+        if body.is_empty() {
+            return Err(Parser::error_at(
+                &open,
+                "repeat body must contain at least one statement".into(),
+            ));
+        }
+        Ok(ProgramBlock::Loop { count, body })
+    }
+}
+
+/**
+ * Compiles a Program Written in the Text Format, e.g.
+ *
+ * ```text
+ * repeat 4 {
+ *     input;
+ *     time 25m;
+ * }
+ * ```
+ */
+pub fn parse_program(source: &str) -> Result<ParsedProgram, ParseError> {
+    let blocks = parse_blocks(source)?;
+    // The Parser Already Checks Loop Counts and Bodies, so This Only Fails on a Parser Bug
+    let (phases, variables) = compile(&blocks).map_err(|error| ParseError {
+        line: 1,
+        column: 1,
+        message: error.to_string(),
+    })?;
+    Ok(ParsedProgram { phases, variables })
+}
+
+/**
+ * Parses the Text Format Into Blocks Without Flattening the Loops
+ */
+pub fn parse_blocks(source: &str) -> Result<Vec<ProgramBlock>, ParseError> {
+    let mut parser = Parser {
+        tokens: tokenize(source)?,
+        position: 0,
+    };
+    parser.statements(TokenKind::End)
+}
+
+#[cfg(test)]
+mod parser_util_tests {
+
+    use super::{parse_program, ParseError, ParsedProgram};
+    use crate::audio_util::Sound;
+    use crate::timer_util::TimerFSM;
+    use crate::ProgramPhase::*;
+    use crate::{TimerInput, TimerStatus};
+
+    #[test]
+    fn simple_statements() {
+        assert_eq!(
+            ParsedProgram {
+                phases: vec![
+                    ReceiveInput { label: None },
+                    TimeFor {
+                        duration: 90,
+                        label: None
+                    }
+                ],
+                variables: vec![],
+            },
+            parse_program("input;\ntime 90;").unwrap()
+        );
+    }
+
+    #[test]
+    fn duration_units() {
+        assert_eq!(
+            vec![TimeFor {
+                duration: 3600 + 30 * 60 + 15,
+                label: None
+            }],
+            parse_program("time 1h 30m 15s;").unwrap().phases
+        );
+    }
+
+    #[test]
+    fn labels_and_text() {
+        assert_eq!(
+            vec![
+                DisplayText {
+                    text: "Set \"A\"".into()
+                },
+                ReceiveInput {
+                    label: Some("Squats".into())
+                },
+                TimeFor {
+                    duration: 20,
+                    label: Some("Plank".into())
+                },
+            ],
+            parse_program(
+                r#"text "Set \"A\"";
+                input "Squats";
+                time 20s "Plank";"#
+            )
+            .unwrap()
+            .phases
+        );
+        assert_eq!(
+            "1:5: expected a string, found ';'",
+            parse_program("text;").unwrap_err().to_string()
+        );
+        assert_eq!(
+            "1:7: unterminated string",
+            parse_program("input \"Squats;\ntime 5;")
+                .unwrap_err()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn sound_statement() {
+        assert_eq!(
+            vec![PlaySound {
+                sound: Sound::TimerDone
+            }],
+            parse_program("sound timer_done;").unwrap().phases
+        );
+        assert_eq!(
+            "1:7: unknown sound 'kazoo'",
+            parse_program("sound kazoo;").unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn repeat_block() {
+        assert_eq!(
+            ParsedProgram {
+                phases: vec![
+                    SetVariable {
+                        var_index: 0,
+                        value: 4
+                    },
+                    ReceiveInput { label: None },
+                    TimeFor {
+                        duration: 25 * 60,
+                        label: None
+                    },
+                    OffsetVariable {
+                        var_index: 0,
+                        offset: -1
+                    },
+                    Repeat {
+                        to_phase: 1,
+                        var_index: 0
+                    },
+                ],
+                variables: vec![4],
+            },
+            parse_program("repeat 4 { input; time 25m; }").unwrap()
+        );
+    }
+
+    #[test]
+    fn nested_repeat_blocks() {
+        let program = parse_program(
+            "# warm up
+            time 5m;
+            repeat 2 {
+                repeat 3 { input; } // inner
+                time 30s;
+            }",
+        )
+        .unwrap();
+        assert_eq!(vec![2, 3], program.variables);
+        assert_eq!(
+            vec![
+                TimeFor {
+                    duration: 300,
+                    label: None
+                },
+                SetVariable {
+                    var_index: 0,
+                    value: 2
+                },
+                SetVariable {
+                    var_index: 1,
+                    value: 3
+                },
+                ReceiveInput { label: None },
+                OffsetVariable {
+                    var_index: 1,
+                    offset: -1
+                },
+                Repeat {
+                    to_phase: 3,
+                    var_index: 1
+                },
+                TimeFor {
+                    duration: 30,
+                    label: None
+                },
+                OffsetVariable {
+                    var_index: 0,
+                    offset: -1
+                },
+                Repeat {
+                    to_phase: 2,
+                    var_index: 0
+                },
+            ],
+            program.phases
+        );
+    }
+
+    #[test]
+    fn nested_repeat_runs_inner_loop_every_time() {
+        let program = parse_program("repeat 2 { repeat 3 { input; } }").unwrap();
+        let mut model = TimerFSM::new(program.phases, Some(program.variables)).unwrap();
+        model.input(TimerInput::Start);
+        let mut inputs = 0;
+        while model.status() == TimerStatus::AwaitingInput {
+            model.input(TimerInput::Input);
+            inputs += 1;
+        }
+        assert_eq!(6, inputs);
+        assert_eq!(TimerStatus::Idle, model.status());
+    }
+
+    #[test]
+    fn unknown_statement_position() {
+        assert_eq!(
+            ParseError {
+                line: 2,
+                column: 5,
+                message: "unknown statement 'wait', expected input, time, text, sound or repeat"
+                    .into()
+            },
+            parse_program("input;\n    wait 5;").unwrap_err()
+        );
+    }
+
+    #[test]
+    fn missing_semicolon() {
+        let error = parse_program("time 5m\ninput;").unwrap_err();
+        assert_eq!((2, 1), (error.line, error.column));
+        assert_eq!("expected ';', found 'input'", error.message);
+    }
+
+    #[test]
+    fn unclosed_repeat() {
+        let error = parse_program("repeat 2 {\n  input;\n").unwrap_err();
+        assert_eq!((3, 1), (error.line, error.column));
+        assert_eq!("expected a statement, found end of input", error.message);
+    }
+
+    #[test]
+    fn invalid_repeat_count() {
+        assert_eq!(
+            "1:8: repeat count must be between 1 and 127, found 200",
+            parse_program("repeat 200 { input; }")
+                .unwrap_err()
+                .to_string()
+        );
+        assert!(parse_program("repeat 0 { input; }").is_err());
+        assert!(parse_program("repeat 2 { }").is_err());
+    }
+
+    #[test]
+    fn invalid_duration_unit() {
+        let error = parse_program("time 5x;").unwrap_err();
+        assert_eq!((1, 7), (error.line, error.column));
+    }
+}
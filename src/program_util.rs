@@ -0,0 +1,286 @@
+use crate::block_util::{compile, BlockError, ProgramBlock};
+use crate::parser_util::{parse_program, ParseError};
+use crate::validator_util::ValidationError;
+use crate::ProgramPhase;
+use crate::TimerFSM;
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/**
+ * Written to Every JSON and TOML Program File; Files From a Newer Version Are Refused on Load
+ */
+pub const PROGRAM_FORMAT_VERSION: u32 = 1;
+
+/**
+ * A Complete Program: the Phases to Run, the Initial Variables and Some Metadata
+ */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Program {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub variables: Vec<i8>,
+    pub phases: Vec<ProgramPhase>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ProgramFile {
+    version: u32,
+    #[serde(flatten)]
+    program: Program,
+}
+
+#[derive(Debug)]
+pub enum ProgramFileError {
+    Json(serde_json::Error),
+    TomlRead(toml::de::Error),
+    TomlWrite(toml::ser::Error),
+    Io(std::io::Error),
+    UnsupportedVersion(u32),
+    UnknownFormat(String),
+}
+
+impl fmt::Display for ProgramFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProgramFileError::Json(error) => write!(f, "invalid program JSON: {}", error),
+            ProgramFileError::TomlRead(error) => write!(f, "invalid program TOML: {}", error),
+            ProgramFileError::TomlWrite(error) => write!(f, "could not write TOML: {}", error),
+            ProgramFileError::Io(error) => write!(f, "{}", error),
+            ProgramFileError::UnsupportedVersion(version) => write!(
+                f,
+                "program format version {} is newer than the supported version {}",
+                version, PROGRAM_FORMAT_VERSION
+            ),
+            ProgramFileError::UnknownFormat(path) => {
+                write!(f, "'{}' is not a .json or .toml program", path)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProgramFileError {}
+
+impl Program {
+    pub fn new(name: &str, phases: Vec<ProgramPhase>, variables: Vec<i8>) -> Program {
+        Program {
+            name: name.into(),
+            description: String::new(),
+            variables,
+            phases,
+        }
+    }
+
+    /**
+     * Compiles a Program Written in the Text Format of parser_util
+     */
+    pub fn from_text(name: &str, source: &str) -> Result<Program, ParseError> {
+        let parsed = parse_program(source)?;
+        Ok(Program::new(name, parsed.phases, parsed.variables))
+    }
+
+    /**
+     * Compiles a Program Written as Blocks, Allocating One Counter per Loop
+     */
+    pub fn from_blocks(name: &str, blocks: &[ProgramBlock]) -> Result<Program, BlockError> {
+        let (phases, variables) = compile(blocks)?;
+        Ok(Program::new(name, phases, variables))
+    }
+
+    pub fn to_fsm(&self) -> Result<TimerFSM, ValidationError> {
+        TimerFSM::new(self.phases.clone(), Some(self.variables.clone()))
+    }
+
+    fn to_file(&self) -> ProgramFile {
+        ProgramFile {
+            version: PROGRAM_FORMAT_VERSION,
+            program: self.clone(),
+        }
+    }
+
+    fn from_file(file: ProgramFile) -> Result<Program, ProgramFileError> {
+        if file.version > PROGRAM_FORMAT_VERSION {
+            return Err(ProgramFileError::UnsupportedVersion(file.version));
+        }
+        Ok(file.program)
+    }
+
+    pub fn to_json(&self) -> Result<String, ProgramFileError> {
+        serde_json::to_string_pretty(&self.to_file()).map_err(ProgramFileError::Json)
+    }
+
+    pub fn from_json(source: &str) -> Result<Program, ProgramFileError> {
+        Program::from_file(serde_json::from_str(source).map_err(ProgramFileError::Json)?)
+    }
+
+    pub fn to_toml(&self) -> Result<String, ProgramFileError> {
+        toml::to_string(&self.to_file()).map_err(ProgramFileError::TomlWrite)
+    }
+
+    pub fn from_toml(source: &str) -> Result<Program, ProgramFileError> {
+        Program::from_file(toml::from_str(source).map_err(ProgramFileError::TomlRead)?)
+    }
+
+    /**
+     * Reads a Program, Picking JSON or TOML from the File Extension
+     */
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(path: &std::path::Path) -> Result<Program, ProgramFileError> {
+        let source = std::fs::read_to_string(path).map_err(ProgramFileError::Io)?;
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Program::from_json(&source),
+            Some("toml") => Program::from_toml(&source),
+            _ => Err(ProgramFileError::UnknownFormat(path.display().to_string())),
+        }
+    }
+
+    /**
+     * Writes the Program, Picking JSON or TOML from the File Extension
+     */
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save(&self, path: &std::path::Path) -> Result<(), ProgramFileError> {
+        let contents = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => self.to_json()?,
+            Some("toml") => self.to_toml()?,
+            _ => return Err(ProgramFileError::UnknownFormat(path.display().to_string())),
+        };
+        std::fs::write(path, contents).map_err(ProgramFileError::Io)
+    }
+}
+
+#[cfg(test)]
+mod program_util_tests {
+
+    use super::{Program, ProgramFileError, PROGRAM_FORMAT_VERSION};
+    use crate::ProgramPhase::*;
+
+    fn example_program() -> Program {
+        let mut program = Program::new(
+            "Pomodoro",
+            vec![
+                ReceiveInput { label: None },
+                TimeFor {
+                    duration: 25 * 60,
+                    label: None,
+                },
+                OffsetVariable {
+                    var_index: 0,
+                    offset: -1,
+                },
+                Repeat {
+                    to_phase: 0,
+                    var_index: 0,
+                },
+            ],
+            vec![4],
+        );
+        program.description = "Four focus blocks".into();
+        program
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let program = example_program();
+        assert_eq!(
+            program,
+            Program::from_json(&program.to_json().unwrap()).unwrap()
+        );
+    }
+
+    #[test]
+    fn toml_round_trip() {
+        let program = example_program();
+        assert_eq!(
+            program,
+            Program::from_toml(&program.to_toml().unwrap()).unwrap()
+        );
+    }
+
+    #[test]
+    fn json_layout() {
+        let json: serde_json::Value =
+            serde_json::from_str(&example_program().to_json().unwrap()).unwrap();
+        assert_eq!(
+            PROGRAM_FORMAT_VERSION,
+            json["version"].as_u64().unwrap() as u32
+        );
+        assert_eq!("Pomodoro", json["name"]);
+        assert_eq!("receive_input", json["phases"][0]["type"]);
+        assert_eq!(1500, json["phases"][1]["duration"]);
+    }
+
+    #[test]
+    fn from_text() {
+        let mut program = example_program();
+        program.description = String::new();
+        // Compiled Loops Reset Their Counter on Entry and Jump Back Past the Reset
+        program.phases.insert(
+            0,
+            SetVariable {
+                var_index: 0,
+                value: 4,
+            },
+        );
+        program.phases[4] = Repeat {
+            to_phase: 1,
+            var_index: 0,
+        };
+        assert_eq!(
+            program,
+            Program::from_text("Pomodoro", "repeat 4 { input; time 25m; }").unwrap()
+        );
+    }
+
+    #[test]
+    fn toml_defaults() {
+        let program = Program::from_toml(
+            r#"
+            version = 1
+            name = "Single"
+
+            [[phases]]
+            type = "time_for"
+            duration = 60
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            Program::new(
+                "Single",
+                vec![TimeFor {
+                    duration: 60,
+                    label: None
+                }],
+                vec![]
+            ),
+            program
+        );
+    }
+
+    #[test]
+    fn newer_version_rejected() {
+        assert!(matches!(
+            Program::from_json(r#"{ "version": 99, "name": "Future", "phases": [] }"#),
+            Err(ProgramFileError::UnsupportedVersion(99))
+        ));
+    }
+
+    #[test]
+    fn missing_version_rejected() {
+        assert!(matches!(
+            Program::from_json(r#"{ "name": "Unversioned", "phases": [] }"#),
+            Err(ProgramFileError::Json(_))
+        ));
+    }
+
+    #[test]
+    fn unknown_extension_rejected() {
+        // Checked Before Anything Is Written
+        assert!(matches!(
+            example_program().save(std::path::Path::new("program.txt")),
+            Err(ProgramFileError::UnknownFormat(_))
+        ));
+    }
+}
@@ -0,0 +1,421 @@
+use crate::history_util::{History, HistoryEvent, Run};
+use crate::ProgramPhase;
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+const MILLIS_PER_DAY: u64 = 24 * 60 * 60 * 1000;
+
+/**
+ * How a Timed Phase Ended: Ran Out, the User Skipped It, or the Whole Run Was Stopped During It
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PhaseOutcome {
+    Completed,
+    Skipped,
+    Stopped,
+}
+
+impl PhaseOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PhaseOutcome::Completed => "completed",
+            PhaseOutcome::Skipped => "skipped",
+            PhaseOutcome::Stopped => "stopped",
+        }
+    }
+}
+
+/**
+ * One Timed Phase as It Actually Ran, Pieced Together From a Run's Events
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhaseRecord {
+    pub label: Option<String>,
+    /// The Duration Given by the Program's TimeFor
+    pub planned: Duration,
+    pub started_at: u64,
+    pub ended_at: u64,
+    /// Time Spent Counting Down, Leaving Out Pauses
+    pub active: Duration,
+    pub outcome: PhaseOutcome,
+    /// Start and End of Each Pause, in Milliseconds Since the Unix Epoch
+    pub pauses: Vec<(u64, u64)>,
+}
+
+/**
+ * Whether a Timer Is a Break Rather Than Focus, Going by the Labels the Templates Use
+ *
+ * Whole words only, so "Short Break" and "rest" count but "Forest" or "Interest" do not.
+ */
+pub fn is_break(label: Option<&str>) -> bool {
+    label.is_some_and(|label| {
+        label
+            .split(|character: char| !character.is_alphanumeric())
+            .any(|word| word.eq_ignore_ascii_case("break") || word.eq_ignore_ascii_case("rest"))
+    })
+}
+
+/**
+ * The Timed Phases of a Run, in the Order They Ran
+ */
+pub fn phase_records(run: &Run) -> Vec<PhaseRecord> {
+    let mut records: Vec<PhaseRecord> = vec![];
+    let mut current: Option<PhaseRecord> = None;
+    let mut paused_at: Option<u64> = None;
+    // A Reset Starts the Countdown Over, so Only Time From Then Counts as Active
+    let mut counted_from: u64 = 0;
+    for entry in &run.events {
+        let ended = match &entry.event {
+            HistoryEvent::PhaseChange {
+                completed: true, ..
+            } => Some(PhaseOutcome::Completed),
+            HistoryEvent::PhaseChange { .. } => Some(PhaseOutcome::Skipped),
+            HistoryEvent::Stopped { .. } => Some(PhaseOutcome::Stopped),
+            HistoryEvent::Paused => {
+                paused_at = Some(entry.at);
+                None
+            }
+            HistoryEvent::Resumed => {
+                if let (Some(record), Some(start)) = (current.as_mut(), paused_at.take()) {
+                    record.pauses.push((start, entry.at));
+                }
+                None
+            }
+            HistoryEvent::Reset => {
+                counted_from = entry.at;
+                None
+            }
+        };
+        // Only an Ending Event Closes the Current Record; Pauses Leave It Open
+        let closed = ended.and_then(|outcome| current.take().map(|record| (outcome, record)));
+        if let Some((outcome, mut record)) = closed {
+            if let Some(start) = paused_at.take() {
+                record.pauses.push((start, entry.at));
+            }
+            record.ended_at = entry.at;
+            record.outcome = outcome;
+            let from = counted_from.max(record.started_at);
+            let paused: u64 = record
+                .pauses
+                .iter()
+                .map(|(start, end)| end.saturating_sub(*start.max(&from)))
+                .sum();
+            record.active = Duration::from_millis(entry.at.saturating_sub(from).saturating_sub(paused));
+            records.push(record);
+        }
+        if let HistoryEvent::PhaseChange {
+            next_phase: ProgramPhase::TimeFor { duration, label },
+            ..
+        } = &entry.event
+        {
+            current = Some(PhaseRecord {
+                label: label.clone(),
+                planned: Duration::from_secs(*duration as u64),
+                started_at: entry.at,
+                ended_at: entry.at,
+                active: Duration::ZERO,
+                // Replaced by the Event That Ends It; a Record Still Open Is Never Returned
+                outcome: PhaseOutcome::Stopped,
+                pauses: vec![],
+            });
+        }
+    }
+    records
+}
+
+/**
+ * Days Since the Unix Epoch on the Calendar of a Zone `utc_offset` Milliseconds Ahead of UTC
+ */
+pub fn day_of(millis: u64, utc_offset: i64) -> i64 {
+    (millis as i64 + utc_offset).div_euclid(MILLIS_PER_DAY as i64)
+}
+
+/**
+ * How Far the User's Local Time Is Ahead of UTC at `millis`, Following Daylight Saving
+ */
+#[cfg(not(target_arch = "wasm32"))]
+pub fn utc_offset(millis: u64) -> i64 {
+    use chrono::{Local, Offset, TimeZone};
+    Local
+        .timestamp_millis_opt(millis as i64)
+        .earliest()
+        .map(|local| local.offset().fix().local_minus_utc() as i64 * 1000)
+        .unwrap_or_default()
+}
+
+/**
+ * How Far the Browser's Local Time Is Ahead of UTC at `millis`, Following Daylight Saving
+ */
+#[cfg(target_arch = "wasm32")]
+pub fn utc_offset(millis: u64) -> i64 {
+    let date = js_sys::Date::new(&wasm_bindgen::JsValue::from_f64(millis as f64));
+    // getTimezoneOffset Is UTC Minus Local, in Minutes
+    -(date.get_timezone_offset() as i64) * 60 * 1000
+}
+
+/**
+ * The User's Local Day for a Time in Milliseconds Since the Unix Epoch
+ */
+pub fn local_day(millis: u64) -> i64 {
+    day_of(millis, utc_offset(millis))
+}
+
+/**
+ * Weeks Since the Unix Epoch, Starting on Mondays (the Epoch Was a Thursday)
+ */
+pub fn week_of(day: i64) -> i64 {
+    (day + 3).div_euclid(7)
+}
+
+/**
+ * `YYYY-MM-DD` for a Day Since the Unix Epoch
+ */
+pub fn date_string(day: i64) -> String {
+    // Howard Hinnant's civil_from_days
+    let z = day + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", y, m, d)
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Stats {
+    pub runs: usize,
+    pub finished_runs: usize,
+    pub completed_phases: usize,
+    pub skipped_phases: usize,
+    /// Timers Cut Short Because the Run Was Stopped, Rather Than Skipped on Their Own
+    pub stopped_phases: usize,
+    pub average_pauses: f32,
+    /// Active Focus Time per Local Day, Keyed by `day_of`
+    pub focus_by_day: BTreeMap<i64, Duration>,
+    /// Active Focus Time per Week, Keyed by `week_of`
+    pub focus_by_week: BTreeMap<i64, Duration>,
+    /// Active Time on Timers Labelled as Breaks, Which the Focus Totals Leave Out
+    pub break_time: Duration,
+    /// Days in a Row With a Completed Focus Timer, Ending Today or Yesterday
+    pub current_streak: usize,
+    pub longest_streak: usize,
+}
+
+/**
+ * Summarises the Whole History; `today` Is a `day_of` Value and Only Matters for the Current Streak
+ *
+ * Days are split at local midnight, using `utc_offset` for each timer's start.
+ */
+pub fn stats(history: &History, today: i64, utc_offset: impl Fn(u64) -> i64) -> Stats {
+    let mut stats = Stats {
+        runs: history.runs.len(),
+        ..Stats::default()
+    };
+    let mut pauses = 0;
+    let mut active_days: Vec<i64> = vec![];
+    for run in &history.runs {
+        if run.finished {
+            stats.finished_runs += 1;
+        }
+        for record in phase_records(run) {
+            pauses += record.pauses.len();
+            match record.outcome {
+                PhaseOutcome::Completed => stats.completed_phases += 1,
+                PhaseOutcome::Skipped => stats.skipped_phases += 1,
+                PhaseOutcome::Stopped => stats.stopped_phases += 1,
+            }
+            if is_break(record.label.as_deref()) {
+                stats.break_time += record.active;
+                continue;
+            }
+            let day = day_of(record.started_at, utc_offset(record.started_at));
+            *stats.focus_by_day.entry(day).or_default() += record.active;
+            *stats.focus_by_week.entry(week_of(day)).or_default() += record.active;
+            if record.outcome == PhaseOutcome::Completed {
+                active_days.push(day);
+            }
+        }
+    }
+    if stats.runs > 0 {
+        stats.average_pauses = pauses as f32 / stats.runs as f32;
+    }
+
+    active_days.sort();
+    active_days.dedup();
+    let mut streak = 0;
+    for (index, day) in active_days.iter().enumerate() {
+        streak = match index {
+            0 => 1,
+            _ if active_days[index - 1] == day - 1 => streak + 1,
+            _ => 1,
+        };
+        stats.longest_streak = stats.longest_streak.max(streak);
+    }
+    // A Streak Is Still Alive Until a Whole Day Goes By Without a Completed Timer
+    if let Some(&last) = active_days.last() {
+        if last >= today - 1 {
+            stats.current_streak = streak;
+        }
+    }
+    stats
+}
+
+#[cfg(test)]
+mod stats_util_tests {
+
+    use super::{
+        date_string, day_of, is_break, phase_records, stats, week_of, PhaseOutcome, MILLIS_PER_DAY,
+    };
+    use crate::history_util::history_fixtures;
+    use crate::history_util::{History, HistoryEntry, HistoryEvent, Run};
+    use std::time::Duration;
+
+    fn example_run(day: u64) -> Run {
+        history_fixtures::example_run(day * MILLIS_PER_DAY)
+    }
+
+    #[test]
+    fn records_from_events() {
+        let records = phase_records(&example_run(0));
+        assert_eq!(2, records.len());
+        assert_eq!(Some("Focus".into()), records[0].label);
+        assert_eq!(Duration::from_secs(60), records[0].planned);
+        assert_eq!(Duration::from_secs(60), records[0].active);
+        assert_eq!(vec![(20_000, 30_000)], records[0].pauses);
+        assert_eq!(PhaseOutcome::Completed, records[0].outcome);
+        assert_eq!(Duration::from_secs(5), records[1].active);
+        assert_eq!(PhaseOutcome::Skipped, records[1].outcome);
+    }
+
+    #[test]
+    fn stopped_is_not_skipped() {
+        let mut run = example_run(0);
+        // Stop During the Break Instead of Skipping It
+        run.events[4] = HistoryEntry {
+            at: 75_000,
+            event: HistoryEvent::Stopped {
+                phase: history_fixtures::timer(30, "Break"),
+            },
+        };
+        run.finished = false;
+        assert_eq!(PhaseOutcome::Stopped, phase_records(&run)[1].outcome);
+        let summary = stats(&History { runs: vec![run] }, 0, |_| 0);
+        assert_eq!(
+            (1, 0, 1),
+            (
+                summary.completed_phases,
+                summary.skipped_phases,
+                summary.stopped_phases
+            )
+        );
+    }
+
+    #[test]
+    fn summary_and_streaks() {
+        let history = History {
+            runs: vec![
+                example_run(10),
+                example_run(11),
+                example_run(11),
+                example_run(13),
+            ],
+        };
+        let summary = stats(&history, 14, |_| 0);
+        assert_eq!(4, summary.runs);
+        assert_eq!(4, summary.completed_phases);
+        assert_eq!(4, summary.skipped_phases);
+        assert_eq!(1.0, summary.average_pauses);
+        assert_eq!(
+            Some(&Duration::from_secs(120)),
+            summary.focus_by_day.get(&11)
+        );
+        assert_eq!(2, summary.longest_streak);
+        assert_eq!(1, summary.current_streak);
+        assert_eq!(0, stats(&history, 20, |_| 0).current_streak);
+    }
+
+    #[test]
+    fn days_split_at_local_midnight() {
+        let mut late = example_run(10);
+        // Shift the Run to 23:30 UTC, Which Is Already the Next Day an Hour East of UTC
+        let shift = MILLIS_PER_DAY - 30 * 60 * 1000;
+        for entry in late.events.iter_mut() {
+            entry.at += shift;
+        }
+        let history = History { runs: vec![late] };
+        let utc = stats(&history, 11, |_| 0);
+        assert!(utc.focus_by_day.contains_key(&10));
+        let east = stats(&history, 11, |_| 60 * 60 * 1000);
+        assert!(east.focus_by_day.contains_key(&11));
+        assert_eq!(1, east.current_streak);
+    }
+
+    #[test]
+    fn breaks_are_not_focus() {
+        let summary = stats(
+            &History {
+                runs: vec![example_run(0)],
+            },
+            0,
+            |_| 0,
+        );
+        assert_eq!(Some(&Duration::from_secs(60)), summary.focus_by_day.get(&0));
+        assert_eq!(Duration::from_secs(5), summary.break_time);
+        assert_eq!(1, summary.skipped_phases);
+        assert!(is_break(Some("Rest")));
+        assert!(is_break(Some("Short Break")));
+        assert!(is_break(Some("coffee-break")));
+        assert!(!is_break(Some("Focus")));
+        assert!(!is_break(Some("Forest")));
+        assert!(!is_break(Some("Interest")));
+        assert!(!is_break(Some("Breakdown")));
+        assert!(!is_break(None));
+    }
+
+    #[test]
+    fn reset_restarts_active_time() {
+        let mut run = example_run(0);
+        // Reset 40s in, Which Is After the Pause; Only the 30s From Then On Count
+        run.events.insert(
+            3,
+            HistoryEntry {
+                at: 40_000,
+                event: HistoryEvent::Reset,
+            },
+        );
+        let records = phase_records(&run);
+        assert_eq!(Duration::from_secs(30), records[0].active);
+        assert_eq!(1, records[0].pauses.len());
+        assert_eq!(Duration::from_secs(5), records[1].active);
+    }
+
+    #[test]
+    fn clock_stepped_back() {
+        let mut run = example_run(0);
+        // The Break Ends Before It Started, e.g. After an NTP Correction
+        run.events[4].at = 65_000;
+        let records = phase_records(&run);
+        assert_eq!(Duration::ZERO, records[1].active);
+        assert_eq!(Duration::from_secs(60), records[0].active);
+    }
+
+    #[test]
+    fn calendar() {
+        assert_eq!("1970-01-01", date_string(0));
+        assert_eq!("2024-02-29", date_string(19_782));
+        assert_eq!(19_782, day_of(19_782 * MILLIS_PER_DAY + 1, 0));
+        // Just After Midnight UTC Is Still the Day Before Five Hours West of It
+        assert_eq!(
+            19_781,
+            day_of(19_782 * MILLIS_PER_DAY + 1, -5 * 60 * 60 * 1000)
+        );
+        // Thursday the First Is in the Week Starting Monday 1969-12-29
+        assert_eq!(0, week_of(0));
+        assert_eq!(0, week_of(3));
+        assert_eq!(1, week_of(4));
+    }
+}
@@ -0,0 +1,176 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+#[derive(Debug)]
+pub enum StoreError {
+    Io(std::io::Error),
+    Storage(String),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::Io(error) => write!(f, "{}", error),
+            StoreError::Storage(reason) => write!(f, "could not use storage: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+/**
+ * Text Kept Between Sessions, Like the History's JSON; Picked per Target by `default_store`
+ */
+pub trait Store {
+    /**
+     * The Saved Text, or None if Nothing Was Saved Yet
+     */
+    fn load(&self) -> Result<Option<String>, StoreError>;
+    fn save(&self, contents: &str) -> Result<(), StoreError>;
+}
+
+/**
+ * Keeps the Text in a File, Creating Its Directory on the First Save
+ */
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone)]
+pub struct FileStore {
+    pub path: std::path::PathBuf,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Store for FileStore {
+    fn load(&self) -> Result<Option<String>, StoreError> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(source) => Ok(Some(source)),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(StoreError::Io(error)),
+        }
+    }
+
+    fn save(&self, contents: &str) -> Result<(), StoreError> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent).map_err(StoreError::Io)?;
+        }
+        std::fs::write(&self.path, contents).map_err(StoreError::Io)
+    }
+}
+
+/**
+ * Keeps the Text Under a localStorage Key
+ */
+#[cfg(target_arch = "wasm32")]
+#[derive(Debug, Clone)]
+pub struct LocalStorageStore {
+    pub key: String,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl LocalStorageStore {
+    fn storage() -> Result<web_sys::Storage, StoreError> {
+        web_sys::window()
+            .and_then(|window| window.local_storage().ok().flatten())
+            .ok_or_else(|| StoreError::Storage("localStorage is not available".into()))
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Store for LocalStorageStore {
+    fn load(&self) -> Result<Option<String>, StoreError> {
+        LocalStorageStore::storage()?
+            .get_item(&self.key)
+            .map_err(|err| StoreError::Storage(format!("{:?}", err)))
+    }
+
+    fn save(&self, contents: &str) -> Result<(), StoreError> {
+        LocalStorageStore::storage()?
+            .set_item(&self.key, contents)
+            .map_err(|err| StoreError::Storage(format!("{:?}", err)))
+    }
+}
+
+/**
+ * Keeps the Text in Memory Only, for Tests and When There Is No Data Directory; Clones Share It
+ */
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStore {
+    contents: Rc<RefCell<Option<String>>>,
+}
+
+impl Store for MemoryStore {
+    fn load(&self) -> Result<Option<String>, StoreError> {
+        Ok(self.contents.borrow().clone())
+    }
+
+    fn save(&self, contents: &str) -> Result<(), StoreError> {
+        *self.contents.borrow_mut() = Some(contents.to_string());
+        Ok(())
+    }
+}
+
+/**
+ * `oxidoro` Under the Platform's Data Directory, Where Every Store File Lives
+ */
+#[cfg(not(target_arch = "wasm32"))]
+pub fn data_dir() -> Option<std::path::PathBuf> {
+    let data_dir = std::env::var_os("XDG_DATA_HOME")
+        .map(std::path::PathBuf::from)
+        .or_else(|| std::env::var_os("APPDATA").map(std::path::PathBuf::from))
+        .or_else(|| {
+            std::env::var_os("HOME")
+                .map(|home| std::path::PathBuf::from(home).join(".local").join("share"))
+        })?;
+    Some(data_dir.join("oxidoro"))
+}
+
+/**
+ * The Store for `file_name` in `data_dir`, or in Memory if the Platform Has No Data Directory
+ */
+#[cfg(not(target_arch = "wasm32"))]
+pub fn default_store(file_name: &str) -> Box<dyn Store> {
+    match data_dir() {
+        Some(dir) => Box::new(FileStore {
+            path: dir.join(file_name),
+        }),
+        None => Box::new(MemoryStore::default()),
+    }
+}
+
+/**
+ * The Store for `file_name` in the Browser: the `oxidoro-<name>` localStorage Key, Without Extension
+ */
+#[cfg(target_arch = "wasm32")]
+pub fn default_store(file_name: &str) -> Box<dyn Store> {
+    let name = file_name.split('.').next().unwrap_or(file_name);
+    Box::new(LocalStorageStore {
+        key: format!("oxidoro-{}", name),
+    })
+}
+
+#[cfg(test)]
+mod store_util_tests {
+
+    use super::{FileStore, MemoryStore, Store};
+
+    #[test]
+    fn memory_store() {
+        let store = MemoryStore::default();
+        assert_eq!(None, store.load().unwrap());
+        store.clone().save("saved").unwrap();
+        assert_eq!(Some("saved".to_string()), store.load().unwrap());
+    }
+
+    #[test]
+    fn file_store() {
+        let path = std::env::temp_dir()
+            .join(format!("oxidoro-store-test-{}", std::process::id()))
+            .join("nested")
+            .join("store.json");
+        let store = FileStore { path: path.clone() };
+        assert_eq!(None, store.load().unwrap());
+        store.save("{}").unwrap();
+        assert_eq!(Some("{}".to_string()), store.load().unwrap());
+        std::fs::remove_dir_all(path.parent().unwrap().parent().unwrap()).unwrap();
+    }
+}
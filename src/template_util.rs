@@ -0,0 +1,259 @@
+use crate::block_util::{BlockError, ProgramBlock};
+use crate::program_util::Program;
+use crate::ProgramPhase;
+
+const MINUTE: usize = 60;
+
+fn timer(duration: usize, label: &str) -> ProgramBlock {
+    ProgramPhase::TimeFor {
+        duration,
+        label: Some(label.into()),
+    }
+    .into()
+}
+
+fn described(
+    name: &str,
+    description: String,
+    blocks: &[ProgramBlock],
+) -> Result<Program, BlockError> {
+    let mut program = Program::from_blocks(name, blocks)?;
+    program.description = description;
+    Ok(program)
+}
+
+/**
+ * `rounds` Focus Timers With a Short Break Between Each and a Long Break After the Last
+ *
+ * Durations are in seconds.
+ */
+pub fn pomodoro(
+    focus: usize,
+    short_break: usize,
+    long_break: usize,
+    rounds: i8,
+) -> Result<Program, BlockError> {
+    if rounds < 1 {
+        return Err(BlockError::InvalidLoopCount(rounds));
+    }
+    let mut blocks = vec![];
+    // The Last Focus Timer Is Followed by the Long Break Instead of a Short One
+    if rounds > 1 {
+        blocks.push(ProgramBlock::Loop {
+            count: rounds - 1,
+            body: vec![timer(focus, "Focus"), timer(short_break, "Short Break")],
+        });
+    }
+    blocks.push(timer(focus, "Focus"));
+    blocks.push(timer(long_break, "Long Break"));
+    described(
+        "Pomodoro",
+        format!(
+            "{} x {} min focus, {} min breaks, {} min long break",
+            rounds,
+            focus / MINUTE,
+            short_break / MINUTE,
+            long_break / MINUTE
+        ),
+        &blocks,
+    )
+}
+
+/**
+ * The Classic 25 Minute Pomodoro: Four Rounds, 5 Minute Breaks and a 15 Minute Long Break
+ */
+pub fn classic_pomodoro() -> Program {
+    pomodoro(25 * MINUTE, 5 * MINUTE, 15 * MINUTE, 4).unwrap()
+}
+
+/**
+ * Alternating Work and Rest Timers, `rounds` Times; Durations Are in Seconds
+ */
+pub fn intervals(work: usize, rest: usize, rounds: i8) -> Result<Program, BlockError> {
+    described(
+        "Intervals",
+        format!("{} x {}s work, {}s rest", rounds, work, rest),
+        &[ProgramBlock::Loop {
+            count: rounds,
+            body: vec![timer(work, "Work"), timer(rest, "Rest")],
+        }],
+    )
+}
+
+/**
+ * 52 Minutes of Work Then 17 Minutes Off, `rounds` Times
+ */
+pub fn fifty_two_seventeen(rounds: i8) -> Result<Program, BlockError> {
+    described(
+        "52/17",
+        format!("{} x 52 min work, 17 min break", rounds),
+        &[ProgramBlock::Loop {
+            count: rounds,
+            body: vec![timer(52 * MINUTE, "Work"), timer(17 * MINUTE, "Break")],
+        }],
+    )
+}
+
+/**
+ * Eight Rounds of 20 Seconds Flat Out and 10 Seconds Rest
+ */
+pub fn tabata() -> Program {
+    let mut program = intervals(20, 10, 8).unwrap();
+    program.name = "Tabata".into();
+    program
+}
+
+/**
+ * Every Minute on the Minute: One Minute Timer per Round, Each Ending With the Timer Cue
+ */
+pub fn emom(minutes: i8) -> Result<Program, BlockError> {
+    described(
+        "EMOM",
+        format!("{} rounds, one every minute", minutes),
+        &[ProgramBlock::Loop {
+            count: minutes,
+            body: vec![timer(MINUTE, "EMOM")],
+        }],
+    )
+}
+
+/**
+ * The Templates Offered in the Library, With Their Usual Settings
+ */
+pub fn built_in() -> Vec<Program> {
+    vec![
+        classic_pomodoro(),
+        fifty_two_seventeen(4).unwrap(),
+        tabata(),
+        emom(10).unwrap(),
+        intervals(45, 15, 10).unwrap(),
+    ]
+}
+
+#[cfg(test)]
+mod template_util_tests {
+
+    use super::{
+        built_in, classic_pomodoro, emom, fifty_two_seventeen, intervals, pomodoro, tabata,
+    };
+    use crate::block_util::BlockError;
+    use crate::program_util::Program;
+    use crate::{ProgramPhase, TimerInput, TimerOutput, TimerStatus};
+    use std::time::Duration;
+
+    /**
+     * Runs a Program to the End, Stepping Each Timer Straight to Zero, and Lists the Timers It Ran
+     */
+    fn run(program: &Program) -> Vec<(String, usize)> {
+        let mut model = program.to_fsm().unwrap();
+        let mut now = Duration::ZERO;
+        let mut timers = vec![];
+        let mut output = model.input_at(TimerInput::Start, now);
+        loop {
+            if let TimerOutput::PhaseChange {
+                next_phase: ProgramPhase::TimeFor { duration, label },
+                ..
+            } = &output
+            {
+                timers.push((label.clone().unwrap_or_default(), *duration));
+            }
+            output = match model.status() {
+                TimerStatus::Idle => break,
+                TimerStatus::AwaitingInput => model.input_at(TimerInput::Input, now),
+                _ => {
+                    now += model.remaining_at(now).unwrap_or_default();
+                    model.input_at(TimerInput::Step, now)
+                }
+            };
+        }
+        assert_eq!(
+            program.to_fsm().unwrap().total_duration(),
+            Some(Duration::from_secs(
+                timers.iter().map(|(_, duration)| *duration as u64).sum()
+            ))
+        );
+        timers
+    }
+
+    fn labels(timers: &[(String, usize)]) -> Vec<&str> {
+        timers.iter().map(|(label, _)| label.as_str()).collect()
+    }
+
+    #[test]
+    fn classic_pomodoro_runs() {
+        let timers = run(&classic_pomodoro());
+        assert_eq!(
+            vec![
+                "Focus",
+                "Short Break",
+                "Focus",
+                "Short Break",
+                "Focus",
+                "Short Break",
+                "Focus",
+                "Long Break"
+            ],
+            labels(&timers)
+        );
+        assert_eq!(("Focus".to_string(), 25 * 60), timers[0]);
+        assert_eq!(("Long Break".to_string(), 15 * 60), timers[7]);
+        assert_eq!(
+            vec!["Focus", "Long Break"],
+            labels(&run(&pomodoro(10, 1, 2, 1).unwrap()))
+        );
+        assert!(matches!(
+            pomodoro(10, 1, 2, 0),
+            Err(BlockError::InvalidLoopCount(0))
+        ));
+    }
+
+    #[test]
+    fn fifty_two_seventeen_runs() {
+        let timers = run(&fifty_two_seventeen(2).unwrap());
+        assert_eq!(vec!["Work", "Break", "Work", "Break"], labels(&timers));
+        assert_eq!(("Break".to_string(), 17 * 60), timers[1]);
+    }
+
+    #[test]
+    fn tabata_runs() {
+        let timers = run(&tabata());
+        assert_eq!(16, timers.len());
+        assert_eq!(("Work".to_string(), 20), timers[0]);
+        assert_eq!(("Rest".to_string(), 10), timers[15]);
+    }
+
+    #[test]
+    fn emom_runs() {
+        let timers = run(&emom(5).unwrap());
+        assert_eq!(vec![("EMOM".to_string(), 60); 5], timers);
+    }
+
+    #[test]
+    fn intervals_run() {
+        let timers = run(&intervals(30, 15, 3).unwrap());
+        assert_eq!(
+            vec!["Work", "Rest", "Work", "Rest", "Work", "Rest"],
+            labels(&timers)
+        );
+        assert!(matches!(
+            intervals(30, 15, -1),
+            Err(BlockError::InvalidLoopCount(-1))
+        ));
+    }
+
+    #[test]
+    fn built_ins_are_valid_and_unique() {
+        let programs = built_in();
+        for program in &programs {
+            assert!(program.to_fsm().is_ok(), "{} is invalid", program.name);
+            assert!(!program.description.is_empty());
+        }
+        let mut names: Vec<&str> = programs
+            .iter()
+            .map(|program| program.name.as_str())
+            .collect();
+        names.sort();
+        names.dedup();
+        assert_eq!(programs.len(), names.len());
+    }
+}
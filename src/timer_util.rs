@@ -1,6 +1,14 @@
+use crate::audio_util::Sound;
+use crate::clock_util::{Clock, SystemClock};
+use crate::duration_util::{timed_from, total_duration};
+use crate::validator_util::{check, ValidationError};
 use crate::TimerInput;
+use crate::TimerStatus;
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum TimerOutput {
     NoChange,
     ProgramStopped {
@@ -12,33 +20,96 @@ pub enum TimerOutput {
         phase_completed: bool,
     },
     TimerProgress {
-        seconds: usize,
+        remaining: Duration,
     },
     TimerPaused,
     TimerReset {
-        seconds: usize,
+        remaining: Duration,
     },
     TimerResumed {
-        seconds: usize,
+        remaining: Duration,
+    },
+    PlaySound {
+        sound: Sound,
+    },
+    DisplayText {
+        text: String,
     },
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
 pub enum ProgramPhase {
     BeginProgram,
-    TimeFor { duration: usize },
-    ReceiveInput,
-    Repeat { to_phase: usize, var_index: usize },
-    OffsetVariable { var_index: usize, offset: i8 },
+    TimeFor {
+        duration: usize,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        label: Option<String>,
+    },
+    ReceiveInput {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        label: Option<String>,
+    },
+    Repeat {
+        to_phase: usize,
+        var_index: usize,
+    },
+    OffsetVariable {
+        var_index: usize,
+        offset: i8,
+    },
+    SetVariable {
+        var_index: usize,
+        value: i8,
+    },
+    PlaySound {
+        sound: Sound,
+    },
+    DisplayText {
+        text: String,
+    },
     EndProgram,
 }
 
+impl ProgramPhase {
+    /**
+     * The Label Shown While a Waiting Phase Is Active, if It Has One
+     */
+    pub fn label(&self) -> Option<&str> {
+        match self {
+            ProgramPhase::TimeFor { label, .. } | ProgramPhase::ReceiveInput { label } => {
+                label.as_deref()
+            }
+            _ => None,
+        }
+    }
+}
+
+/**
+ * Everything Needed to Draw the Timer at One Moment, e.g. After a Page Reload
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimerSnapshot {
+    pub status: TimerStatus,
+    pub phase_index: usize,
+    /// None While Idle
+    pub phase: Option<ProgramPhase>,
+    /// Only Set While a Timer Phase Is Active
+    pub remaining: Option<Duration>,
+    pub elapsed: Option<Duration>,
+    /// Timed Length Left in the Whole Program, None if It Is Too Long to Estimate
+    pub program_remaining: Option<Duration>,
+    pub program_total: Option<Duration>,
+    pub variables: Vec<i8>,
+    pub program_len: usize,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 enum TimerState {
     Idle,
     Timer {
-        progress: usize,
-        duration: usize,
+        remaining: Duration,
+        duration: Duration,
         paused: bool,
     },
     Input,
@@ -46,105 +117,325 @@ enum TimerState {
 
 pub struct TimerFSM {
     program: Vec<ProgramPhase>,
+    /// The Variables as Given, Restored Whenever the Program Starts Over
+    initial_variables: Vec<i8>,
     variables: Vec<i8>,
     state: TimerState,
     phase: usize,
+    /// Monotonic Time of the Last Input, Which the Running Timer's Remaining Time Is Relative To
+    last_update: Duration,
+    clock: Box<dyn Clock>,
+    /// Outputs Produced While Passing Through Instant Phases, Like PlaySound
+    effects: Vec<TimerOutput>,
+    /// Timed Length of a Full Run, Fixed Since the Program and Initial Variables Are
+    total: Option<Duration>,
+    /// Timed Length of the Phases After the Current One, Recomputed Only on Phase Changes
+    rest: Option<Duration>,
 }
 
 /**
  * @return the State the Timer Should Be In After Transitioning to the Given Phase
+ *
+ * Instant phases are passed through in a loop rather than by recursion, since a valid
+ * program can run thousands of them back to back, e.g. nested loops around a sound.
  */
 fn phase_transition(
     phase: &mut usize,
     state: &mut TimerState,
-    variables: &mut Vec<i8>,
-    program: &Vec<ProgramPhase>,
+    variables: &mut [i8],
+    program: &[ProgramPhase],
+    effects: &mut Vec<TimerOutput>,
 ) {
-    return match program.get(*phase) {
-        Some(program_phase) => {
-            match program_phase {
-                ProgramPhase::TimeFor { duration } => {
-                    *state = TimerState::Timer {
-                        progress: *duration,
-                        duration: *duration,
-                        paused: false,
-                    }
-                }
-                ProgramPhase::BeginProgram | ProgramPhase::EndProgram => {
-                    *state = TimerState::Idle;
-                    *phase = 0;
-                }
-                ProgramPhase::ReceiveInput => {
-                    *state = TimerState::Input;
-                }
-                ProgramPhase::Repeat {
-                    to_phase,
-                    var_index,
-                } => {
-                    // Check if variable is zero
-                    if variables[*var_index] == 0 {
-                        *phase += 1;
-                        phase_transition(phase, state, variables, program);
-                    } else {
-                        *phase = *to_phase;
-                        phase_transition(phase, state, variables, program);
-                    }
-                }
-                ProgramPhase::OffsetVariable { var_index, offset } => {
-                    // Edit Variable
-                    variables[*var_index] += offset;
+    loop {
+        let Some(program_phase) = program.get(*phase) else {
+            *state = TimerState::Idle;
+            //*phase = 0;
+            return;
+        };
+        match program_phase {
+            ProgramPhase::TimeFor { duration, .. } => {
+                *state = TimerState::Timer {
+                    remaining: Duration::from_secs(*duration as u64),
+                    duration: Duration::from_secs(*duration as u64),
+                    paused: false,
+                };
+                return;
+            }
+            ProgramPhase::BeginProgram | ProgramPhase::EndProgram => {
+                *state = TimerState::Idle;
+                *phase = 0;
+                return;
+            }
+            ProgramPhase::ReceiveInput { .. } => {
+                *state = TimerState::Input;
+                return;
+            }
+            ProgramPhase::Repeat {
+                to_phase,
+                var_index,
+            } => {
+                // Check if variable is zero
+                if variables[*var_index] == 0 {
                     *phase += 1;
-                    phase_transition(phase, state, variables, program);
+                } else {
+                    *phase = *to_phase;
                 }
             }
+            ProgramPhase::OffsetVariable { var_index, offset } => {
+                // Edit Variable, Saturating Like the Estimate Since the Validator Only Warns About Overflow
+                variables[*var_index] = variables[*var_index].saturating_add(*offset);
+                *phase += 1;
+            }
+            ProgramPhase::SetVariable { var_index, value } => {
+                // Loops Start With This so Their Counter Is Fresh Each Time They Are Entered
+                variables[*var_index] = *value;
+                *phase += 1;
+            }
+            ProgramPhase::PlaySound { sound } => {
+                effects.push(TimerOutput::PlaySound { sound: *sound });
+                *phase += 1;
+            }
+            ProgramPhase::DisplayText { text } => {
+                effects.push(TimerOutput::DisplayText { text: text.clone() });
+                *phase += 1;
+            }
         }
-        None => {
-            *state = TimerState::Idle;
-            //*phase = 0;
-        }
-    };
+    }
 }
 
 impl TimerFSM {
-    pub fn new(program: Vec<ProgramPhase>, variables: Option<Vec<i8>>) -> TimerFSM {
-        TimerFSM {
-            program: program,
+    /**
+     * Validates the Program First, Refusing Any That Would Panic or Hang While Running
+     */
+    pub fn new(
+        program: Vec<ProgramPhase>,
+        variables: Option<Vec<i8>>,
+    ) -> Result<TimerFSM, ValidationError> {
+        TimerFSM::with_clock(program, variables, Box::new(SystemClock))
+    }
+
+    /**
+     * Like new, but Reading Time From the Given Clock, e.g. a ManualClock in Tests
+     */
+    pub fn with_clock(
+        program: Vec<ProgramPhase>,
+        variables: Option<Vec<i8>>,
+        clock: Box<dyn Clock>,
+    ) -> Result<TimerFSM, ValidationError> {
+        let variables = variables.unwrap_or_default();
+        check(&program, &variables)?;
+        Ok(TimerFSM {
+            total: total_duration(&program, &variables),
+            rest: None,
+            program,
             state: TimerState::Idle,
             phase: 0,
-            variables: variables.unwrap_or(vec![]),
+            initial_variables: variables.clone(),
+            variables,
+            last_update: clock.now(),
+            clock,
+            effects: vec![],
+        })
+    }
+
+    /**
+     * What the Program Is Doing, for Enabling the Buttons That Make Sense Right Now
+     */
+    pub fn status(&self) -> TimerStatus {
+        match self.state {
+            TimerState::Idle => TimerStatus::Idle,
+            TimerState::Timer { paused: true, .. } => TimerStatus::Paused,
+            TimerState::Timer { .. } => TimerStatus::Running,
+            TimerState::Input => TimerStatus::AwaitingInput,
+        }
+    }
+
+    /**
+     * Index of the Current Phase; Meaningless While Idle
+     */
+    pub fn phase_index(&self) -> usize {
+        self.phase
+    }
+
+    /**
+     * The Phase Being Waited on, or None While Idle
+     */
+    pub fn current_phase(&self) -> Option<&ProgramPhase> {
+        match self.state {
+            TimerState::Idle => None,
+            _ => self.program.get(self.phase),
+        }
+    }
+
+    pub fn program(&self) -> &[ProgramPhase] {
+        &self.program
+    }
+
+    pub fn program_len(&self) -> usize {
+        self.program.len()
+    }
+
+    /**
+     * Current Loop Counter Values
+     */
+    pub fn variables(&self) -> &[i8] {
+        &self.variables
+    }
+
+    pub fn is_paused(&self) -> bool {
+        matches!(self.state, TimerState::Timer { paused: true, .. })
+    }
+
+    pub fn remaining(&self) -> Option<Duration> {
+        self.remaining_at(self.clock.now())
+    }
+
+    /**
+     * Time Left on the Running Timer at `now`, Counting Time Since the Last Input
+     */
+    pub fn remaining_at(&self, now: Duration) -> Option<Duration> {
+        match self.state {
+            TimerState::Timer {
+                remaining,
+                paused: false,
+                ..
+            } => Some(remaining.saturating_sub(now.saturating_sub(self.last_update))),
+            TimerState::Timer { remaining, .. } => Some(remaining),
+            _ => None,
+        }
+    }
+
+    pub fn elapsed_at(&self, now: Duration) -> Option<Duration> {
+        match self.state {
+            TimerState::Timer { duration, .. } => self
+                .remaining_at(now)
+                .map(|remaining| duration.saturating_sub(remaining)),
+            _ => None,
+        }
+    }
+
+    /**
+     * Timed Length of a Full Run, Not Counting Time Spent Waiting for Input
+     */
+    pub fn total_duration(&self) -> Option<Duration> {
+        self.total
+    }
+
+    /**
+     * Timed Length Left in the Program at `now`, Following the Loops From Their Current Counts
+     */
+    pub fn program_remaining_at(&self, now: Duration) -> Option<Duration> {
+        match self.state {
+            TimerState::Idle => self.total_duration(),
+            TimerState::Timer { .. } => {
+                Some(self.remaining_at(now).unwrap_or_default() + self.rest?)
+            }
+            TimerState::Input => self.rest,
         }
     }
 
+    /**
+     * Follows the Loops From the New Phase Once, so Snapshots on Every Tick Stay Cheap
+     */
+    fn update_rest(&mut self) {
+        self.rest = match self.state {
+            TimerState::Idle => None,
+            _ => timed_from(&self.program, self.phase + 1, &self.variables),
+        };
+    }
+
+    pub fn snapshot(&self) -> TimerSnapshot {
+        self.snapshot_at(self.clock.now())
+    }
+
+    pub fn snapshot_at(&self, now: Duration) -> TimerSnapshot {
+        TimerSnapshot {
+            status: self.status(),
+            phase_index: self.phase,
+            phase: self.current_phase().cloned(),
+            remaining: self.remaining_at(now),
+            elapsed: self.elapsed_at(now),
+            program_remaining: self.program_remaining_at(now),
+            program_total: self.total_duration(),
+            variables: self.variables.clone(),
+            program_len: self.program.len(),
+        }
+    }
+
+    /**
+     * Drains the Outputs Queued by the Last Input Besides the One It Returned, in Order
+     */
+    pub fn take_effects(&mut self) -> Vec<TimerOutput> {
+        std::mem::take(&mut self.effects)
+    }
+
     pub fn input(&mut self, input: TimerInput) -> TimerOutput {
+        let now = self.clock.now();
+        self.input_at(input, now)
+    }
+
+    /**
+     * Handles an Input Arriving at `now` on the Monotonic Clock
+     *
+     * The running timer is brought up to date by the real time since the last input, so
+     * pausing keeps fractions of a second and late or dropped Steps do not make it drift.
+     */
+    pub fn input_at(&mut self, input: TimerInput, now: Duration) -> TimerOutput {
+        let elapsed = now.saturating_sub(self.last_update);
+        self.last_update = now;
+        let mut already_finished = false;
+        if let TimerState::Timer {
+            remaining,
+            paused: false,
+            ..
+        } = &mut self.state
+        {
+            already_finished = remaining.is_zero();
+            *remaining = remaining.saturating_sub(elapsed);
+        }
+
         let mut output: TimerOutput = TimerOutput::NoChange;
         match (&mut self.state, input) {
             (_, TimerInput::Stop) => {
                 output = TimerOutput::ProgramStopped {
-                    program_phase: *self
+                    program_phase: self
                         .program
                         .get(self.phase)
-                        .unwrap_or(&ProgramPhase::EndProgram),
+                        .cloned()
+                        .unwrap_or(ProgramPhase::EndProgram),
                 };
                 self.phase = 0;
+                self.variables = self.initial_variables.clone();
                 phase_transition(
                     &mut self.program.len(),
                     &mut self.state,
                     &mut self.variables,
                     &self.program,
+                    &mut self.effects,
                 );
+                self.update_rest();
             }
             (TimerState::Idle, TimerInput::Start) => {
-                output = TimerOutput::PhaseChange {
-                    prev_phase: ProgramPhase::BeginProgram,
-                    next_phase: self.program[0],
-                    phase_completed: true,
-                };
+                // Also Covers Starting Again After the Program Ran to Its End
+                self.phase = 0;
+                self.variables = self.initial_variables.clone();
                 phase_transition(
                     &mut self.phase,
                     &mut self.state,
                     &mut self.variables,
                     &self.program,
+                    &mut self.effects,
                 );
+                self.update_rest();
+                output = TimerOutput::PhaseChange {
+                    prev_phase: ProgramPhase::BeginProgram,
+                    next_phase: self
+                        .program
+                        .get(self.phase)
+                        .cloned()
+                        .unwrap_or(ProgramPhase::EndProgram),
+                    phase_completed: true,
+                };
             }
             (TimerState::Idle, _) => {}
             (_, TimerInput::Start) => {}
@@ -153,7 +444,7 @@ impl TimerFSM {
             }
             (
                 TimerState::Timer {
-                    progress,
+                    remaining,
                     duration: _,
                     paused,
                 },
@@ -161,27 +452,30 @@ impl TimerFSM {
             ) => {
                 if *paused {
                     output = TimerOutput::NoChange;
-                } else if *progress > 0 {
-                    *progress -= 1;
-                    output = TimerOutput::TimerProgress { seconds: *progress };
+                } else if !already_finished {
+                    output = TimerOutput::TimerProgress {
+                        remaining: *remaining,
+                    };
                 } else {
                     output = self.next_phase(true);
                 }
             }
             (
                 TimerState::Timer {
-                    progress,
+                    remaining,
                     duration,
                     paused: _,
                 },
                 TimerInput::Reset,
             ) => {
-                *progress = *duration;
-                output = TimerOutput::TimerReset { seconds: *progress };
+                *remaining = *duration;
+                output = TimerOutput::TimerReset {
+                    remaining: *remaining,
+                };
             }
             (
                 TimerState::Timer {
-                    progress: _,
+                    remaining: _,
                     duration: _,
                     paused,
                 },
@@ -192,14 +486,16 @@ impl TimerFSM {
             }
             (
                 TimerState::Timer {
-                    progress,
+                    remaining,
                     duration: _,
                     paused,
                 },
                 TimerInput::Resume,
             ) => {
                 *paused = false;
-                output = TimerOutput::TimerResumed { seconds: *progress };
+                output = TimerOutput::TimerResumed {
+                    remaining: *remaining,
+                };
             }
             (TimerState::Timer { .. }, _) => {}
             (TimerState::Input, TimerInput::Input) => {
@@ -207,113 +503,153 @@ impl TimerFSM {
             }
             (TimerState::Input, _) => {}
         }
-        return output;
+        output
     }
 
     fn next_phase(&mut self, prev_completed: bool) -> TimerOutput {
-        let prev_phase: ProgramPhase = self.program[self.phase];
+        let prev_phase: ProgramPhase = self.program[self.phase].clone();
         self.phase += 1;
         phase_transition(
             &mut self.phase,
             &mut self.state,
             &mut self.variables,
             &self.program,
+            &mut self.effects,
         );
-        return TimerOutput::PhaseChange {
-            prev_phase: prev_phase,
-            next_phase: *self
+        self.update_rest();
+        TimerOutput::PhaseChange {
+            prev_phase,
+            next_phase: self
                 .program
                 .get(self.phase)
-                .unwrap_or(&ProgramPhase::EndProgram),
+                .cloned()
+                .unwrap_or(ProgramPhase::EndProgram),
             phase_completed: prev_completed,
-        };
+        }
     }
 }
 
 #[cfg(test)]
 mod timer_util_tests {
 
-    use super::{ProgramPhase, TimerFSM, TimerInput, TimerOutput, TimerState};
+    use super::{
+        ProgramPhase, TimerFSM, TimerInput, TimerOutput, TimerSnapshot, TimerState, TimerStatus,
+    };
+    use crate::audio_util::Sound;
+    use crate::block_util::{compile, ProgramBlock};
+    use crate::validator_util::Diagnostic;
+    use std::time::Duration;
     use ProgramPhase::*;
     use TimerInput::*;
     use TimerOutput::*;
     use TimerState::*;
 
+    fn secs(seconds: usize) -> Duration {
+        Duration::from_secs(seconds as u64)
+    }
+
+    /**
+     * Feeds Step One Second After the Previous Input, Like a Steady One Second Tick
+     */
+    fn step(model: &mut TimerFSM, now: &mut Duration) -> TimerOutput {
+        *now += secs(1);
+        model.input_at(Step, *now)
+    }
+
     #[test]
     fn timer_program() {
         let seconds: usize = 3;
-        let mut model: TimerFSM = TimerFSM::new(vec![TimeFor { duration: seconds }], None);
+        let mut model: TimerFSM = TimerFSM::new(
+            vec![TimeFor {
+                duration: seconds,
+                label: None,
+            }],
+            None,
+        )
+        .unwrap();
+        let mut now = Duration::ZERO;
         assert_eq!(Idle, model.state);
         assert_eq!(
             PhaseChange {
                 prev_phase: BeginProgram,
-                next_phase: TimeFor { duration: seconds },
+                next_phase: TimeFor {
+                    duration: seconds,
+                    label: None
+                },
                 phase_completed: true
             },
-            model.input(Start)
+            model.input_at(Start, now)
         );
         for i in 1..seconds {
             assert_eq!(
                 TimerProgress {
-                    seconds: seconds - i
+                    remaining: secs(seconds - i)
                 },
-                model.input(Step)
+                step(&mut model, &mut now)
             );
         }
-        assert_eq!(TimerProgress { seconds: 0 }, model.input(Step));
+        assert_eq!(
+            TimerProgress { remaining: secs(0) },
+            step(&mut model, &mut now)
+        );
         assert_eq!(
             PhaseChange {
-                prev_phase: TimeFor { duration: seconds },
+                prev_phase: TimeFor {
+                    duration: seconds,
+                    label: None
+                },
                 next_phase: EndProgram,
                 phase_completed: true
             },
-            model.input(Step)
+            step(&mut model, &mut now)
         );
         assert_eq!(Idle, model.state);
     }
 
     #[test]
     fn input_program() {
-        let mut model: TimerFSM = TimerFSM::new(vec![ReceiveInput], None);
+        let mut model: TimerFSM = TimerFSM::new(vec![ReceiveInput { label: None }], None).unwrap();
+        let now = Duration::ZERO;
         assert_eq!(Idle, model.state);
         assert_eq!(
             PhaseChange {
                 prev_phase: BeginProgram,
-                next_phase: ReceiveInput,
+                next_phase: ReceiveInput { label: None },
                 phase_completed: true
             },
-            model.input(TimerInput::Start)
+            model.input_at(TimerInput::Start, now)
         );
         assert_eq!(
             PhaseChange {
-                prev_phase: ReceiveInput,
+                prev_phase: ReceiveInput { label: None },
                 next_phase: EndProgram,
                 phase_completed: true
             },
-            model.input(TimerInput::Input)
+            model.input_at(TimerInput::Input, now)
         );
         assert_eq!(Idle, model.state);
     }
 
     #[test]
     fn skip_input_program() {
-        let mut model: TimerFSM = TimerFSM::new(vec![ReceiveInput], None);
+        let mut model: TimerFSM = TimerFSM::new(vec![ReceiveInput { label: None }], None).unwrap();
+        let now = Duration::ZERO;
         assert_eq!(Idle, model.state);
         assert_eq!(
             PhaseChange {
                 prev_phase: BeginProgram,
-                next_phase: ReceiveInput,
+                next_phase: ReceiveInput { label: None },
                 phase_completed: true
             },
-            model.input(TimerInput::Start)
+            model.input_at(TimerInput::Start, now)
         );
         assert_eq!(
             PhaseChange {
-                prev_phase: ReceiveInput,
+                prev_phase: ReceiveInput { label: None },
                 next_phase: EndProgram,
                 phase_completed: false
             },
-            model.input(TimerInput::Skip)
+            model.input_at(TimerInput::Skip, now)
         );
         assert_eq!(Idle, model.state);
     }
@@ -321,20 +657,31 @@ mod timer_util_tests {
     #[test]
     fn stop_timer_program() {
         let seconds: usize = 3;
-        let mut model: TimerFSM = TimerFSM::new(vec![TimeFor { duration: seconds }], None);
+        let mut model: TimerFSM = TimerFSM::new(
+            vec![TimeFor {
+                duration: seconds,
+                label: None,
+            }],
+            None,
+        )
+        .unwrap();
+        let mut now = Duration::ZERO;
         assert_eq!(Idle, model.state);
         assert_eq!(
             PhaseChange {
                 prev_phase: BeginProgram,
-                next_phase: TimeFor { duration: seconds },
+                next_phase: TimeFor {
+                    duration: seconds,
+                    label: None
+                },
                 phase_completed: true
             },
-            model.input(Start)
+            model.input_at(Start, now)
         );
         assert_eq!(
             Timer {
-                progress: seconds,
-                duration: seconds,
+                remaining: secs(seconds),
+                duration: secs(seconds),
                 paused: false
             },
             model.state
@@ -342,16 +689,19 @@ mod timer_util_tests {
         for i in 1..seconds {
             assert_eq!(
                 TimerProgress {
-                    seconds: seconds - i
+                    remaining: secs(seconds - i)
                 },
-                model.input(Step)
+                step(&mut model, &mut now)
             );
         }
         assert_eq!(
             ProgramStopped {
-                program_phase: TimeFor { duration: seconds }
+                program_phase: TimeFor {
+                    duration: seconds,
+                    label: None
+                }
             },
-            model.input(Stop)
+            model.input_at(Stop, now)
         );
         assert_eq!(Idle, model.state);
         assert_eq!(0, model.phase);
@@ -360,20 +710,31 @@ mod timer_util_tests {
     #[test]
     fn reset_timer_program() {
         let seconds: usize = 3;
-        let mut model: TimerFSM = TimerFSM::new(vec![TimeFor { duration: seconds }], None);
+        let mut model: TimerFSM = TimerFSM::new(
+            vec![TimeFor {
+                duration: seconds,
+                label: None,
+            }],
+            None,
+        )
+        .unwrap();
+        let mut now = Duration::ZERO;
         assert_eq!(Idle, model.state);
         assert_eq!(
             PhaseChange {
                 prev_phase: BeginProgram,
-                next_phase: TimeFor { duration: seconds },
+                next_phase: TimeFor {
+                    duration: seconds,
+                    label: None
+                },
                 phase_completed: true
             },
-            model.input(Start)
+            model.input_at(Start, now)
         );
         assert_eq!(
             Timer {
-                progress: seconds,
-                duration: seconds,
+                remaining: secs(seconds),
+                duration: secs(seconds),
                 paused: false
             },
             model.state
@@ -381,16 +742,21 @@ mod timer_util_tests {
         for i in 1..seconds {
             assert_eq!(
                 TimerProgress {
-                    seconds: seconds - i
+                    remaining: secs(seconds - i)
                 },
-                model.input(Step)
+                step(&mut model, &mut now)
             );
         }
-        assert_eq!(TimerReset { seconds: seconds }, model.input(Reset));
+        assert_eq!(
+            TimerReset {
+                remaining: secs(seconds)
+            },
+            model.input_at(Reset, now)
+        );
         assert_eq!(
             Timer {
-                progress: seconds,
-                duration: seconds,
+                remaining: secs(seconds),
+                duration: secs(seconds),
                 paused: false
             },
             model.state
@@ -403,45 +769,58 @@ mod timer_util_tests {
         let seconds: usize = 3;
         let mut model: TimerFSM = TimerFSM::new(
             vec![
-                TimeFor { duration: seconds },
+                TimeFor {
+                    duration: seconds,
+                    label: None,
+                },
                 TimeFor {
                     duration: seconds + 1,
+                    label: None,
                 },
             ],
             None,
-        );
+        )
+        .unwrap();
+        let now = Duration::ZERO;
         assert_eq!(Idle, model.state);
         assert_eq!(
             PhaseChange {
                 prev_phase: BeginProgram,
-                next_phase: TimeFor { duration: seconds },
+                next_phase: TimeFor {
+                    duration: seconds,
+                    label: None
+                },
                 phase_completed: true
             },
-            model.input(Start)
+            model.input_at(Start, now)
         );
         assert_eq!(
             Timer {
-                progress: seconds,
-                duration: seconds,
+                remaining: secs(seconds),
+                duration: secs(seconds),
                 paused: false
             },
             model.state
         );
         assert_eq!(
             PhaseChange {
-                prev_phase: TimeFor { duration: seconds },
+                prev_phase: TimeFor {
+                    duration: seconds,
+                    label: None
+                },
                 next_phase: TimeFor {
-                    duration: seconds + 1
+                    duration: seconds + 1,
+                    label: None
                 },
                 phase_completed: false
             },
-            model.input(Skip)
+            model.input_at(Skip, now)
         );
         assert_eq!(1, model.phase);
         assert_eq!(
             Timer {
-                progress: seconds + 1,
-                duration: seconds + 1,
+                remaining: secs(seconds + 1),
+                duration: secs(seconds + 1),
                 paused: false
             },
             model.state
@@ -451,39 +830,50 @@ mod timer_util_tests {
     #[test]
     fn pause_timer_program() {
         let seconds: usize = 3;
-        let mut model: TimerFSM = TimerFSM::new(vec![TimeFor { duration: seconds }], None);
+        let mut model: TimerFSM = TimerFSM::new(
+            vec![TimeFor {
+                duration: seconds,
+                label: None,
+            }],
+            None,
+        )
+        .unwrap();
+        let mut now = Duration::ZERO;
         assert_eq!(Idle, model.state);
         assert_eq!(
             PhaseChange {
                 prev_phase: BeginProgram,
-                next_phase: TimeFor { duration: seconds },
+                next_phase: TimeFor {
+                    duration: seconds,
+                    label: None
+                },
                 phase_completed: true
             },
-            model.input(Start)
+            model.input_at(Start, now)
         );
         assert_eq!(
             Timer {
-                progress: seconds,
-                duration: seconds,
+                remaining: secs(seconds),
+                duration: secs(seconds),
                 paused: false
             },
             model.state
         );
-        assert_eq!(TimerPaused, model.input(Pause));
+        assert_eq!(TimerPaused, model.input_at(Pause, now));
         assert_eq!(0, model.phase);
         assert_eq!(
             Timer {
-                progress: seconds,
-                duration: seconds,
+                remaining: secs(seconds),
+                duration: secs(seconds),
                 paused: true
             },
             model.state
         );
-        assert_eq!(NoChange, model.input(Step));
+        assert_eq!(NoChange, step(&mut model, &mut now));
         assert_eq!(
             Timer {
-                progress: seconds,
-                duration: seconds,
+                remaining: secs(seconds),
+                duration: secs(seconds),
                 paused: true
             },
             model.state
@@ -493,44 +883,60 @@ mod timer_util_tests {
     #[test]
     fn resume_timer_program() {
         let seconds: usize = 3;
-        let mut model: TimerFSM = TimerFSM::new(vec![TimeFor { duration: seconds }], None);
+        let mut model: TimerFSM = TimerFSM::new(
+            vec![TimeFor {
+                duration: seconds,
+                label: None,
+            }],
+            None,
+        )
+        .unwrap();
+        let mut now = Duration::ZERO;
         assert_eq!(Idle, model.state);
         assert_eq!(
             PhaseChange {
                 prev_phase: BeginProgram,
-                next_phase: TimeFor { duration: seconds },
+                next_phase: TimeFor {
+                    duration: seconds,
+                    label: None
+                },
                 phase_completed: true
             },
-            model.input(Start)
+            model.input_at(Start, now)
         );
         assert_eq!(
             Timer {
-                progress: seconds,
-                duration: seconds,
+                remaining: secs(seconds),
+                duration: secs(seconds),
                 paused: false
             },
             model.state
         );
-        assert_eq!(TimerPaused, model.input(Pause));
+        assert_eq!(TimerPaused, model.input_at(Pause, now));
         assert_eq!(
             Timer {
-                progress: seconds,
-                duration: seconds,
+                remaining: secs(seconds),
+                duration: secs(seconds),
                 paused: true
             },
             model.state
         );
-        assert_eq!(TimerResumed { seconds: seconds }, model.input(Resume));
+        assert_eq!(
+            TimerResumed {
+                remaining: secs(seconds)
+            },
+            model.input_at(Resume, now)
+        );
         assert_eq!(
             TimerProgress {
-                seconds: seconds - 1
+                remaining: secs(seconds - 1)
             },
-            model.input(Step)
+            step(&mut model, &mut now)
         );
         assert_eq!(
             Timer {
-                progress: seconds - 1,
-                duration: seconds,
+                remaining: secs(seconds - 1),
+                duration: secs(seconds),
                 paused: false
             },
             model.state
@@ -543,83 +949,106 @@ mod timer_util_tests {
         let seconds: usize = 1;
         let mut model: TimerFSM = TimerFSM::new(
             vec![
-                TimeFor { duration: seconds },
+                TimeFor {
+                    duration: seconds,
+                    label: None,
+                },
                 TimeFor {
                     duration: seconds + 1,
+                    label: None,
                 },
                 TimeFor {
                     duration: seconds + 2,
+                    label: None,
                 },
             ],
             None,
-        );
+        )
+        .unwrap();
+        let mut now = Duration::ZERO;
         assert_eq!(Idle, model.state);
         assert_eq!(
             PhaseChange {
                 prev_phase: BeginProgram,
-                next_phase: TimeFor { duration: seconds },
+                next_phase: TimeFor {
+                    duration: seconds,
+                    label: None
+                },
                 phase_completed: true
             },
-            model.input(Start)
+            model.input_at(Start, now)
         );
         for i in 1..seconds {
             assert_eq!(
                 TimerProgress {
-                    seconds: seconds - i
+                    remaining: secs(seconds - i)
                 },
-                model.input(Step)
+                step(&mut model, &mut now)
             );
         }
-        model.input(Step);
+        step(&mut model, &mut now);
         assert_eq!(
             PhaseChange {
-                prev_phase: TimeFor { duration: seconds },
+                prev_phase: TimeFor {
+                    duration: seconds,
+                    label: None
+                },
                 next_phase: TimeFor {
-                    duration: seconds + 1
+                    duration: seconds + 1,
+                    label: None
                 },
                 phase_completed: true
             },
-            model.input(Step)
+            step(&mut model, &mut now)
         );
         for i in 1..(seconds + 1) {
             assert_eq!(
                 TimerProgress {
-                    seconds: seconds + 1 - i
+                    remaining: secs(seconds + 1 - i)
                 },
-                model.input(Step)
+                step(&mut model, &mut now)
             );
         }
-        assert_eq!(TimerProgress { seconds: 0 }, model.input(Step));
+        assert_eq!(
+            TimerProgress { remaining: secs(0) },
+            step(&mut model, &mut now)
+        );
         assert_eq!(
             PhaseChange {
                 prev_phase: TimeFor {
-                    duration: seconds + 1
+                    duration: seconds + 1,
+                    label: None
                 },
                 next_phase: TimeFor {
-                    duration: seconds + 2
+                    duration: seconds + 2,
+                    label: None
                 },
                 phase_completed: true
             },
-            model.input(Step)
+            step(&mut model, &mut now)
         );
         for i in 1..(seconds + 2) {
             assert_eq!(
                 TimerProgress {
-                    seconds: seconds + 2 - i
+                    remaining: secs(seconds + 2 - i)
                 },
-                model.input(Step)
+                step(&mut model, &mut now)
             );
         }
-        assert_eq!(TimerProgress { seconds: 0 }, model.input(Step));
+        assert_eq!(
+            TimerProgress { remaining: secs(0) },
+            step(&mut model, &mut now)
+        );
         assert_eq!(
             PhaseChange {
                 prev_phase: TimeFor {
-                    duration: seconds + 2
+                    duration: seconds + 2,
+                    label: None
                 },
                 next_phase: EndProgram,
                 phase_completed: true
             },
-            model.input(Step)
+            step(&mut model, &mut now)
         );
         assert_eq!(Idle, model.state);
     }
@@ -628,7 +1057,7 @@ mod timer_util_tests {
     fn loop_program() {
         let mut model: TimerFSM = TimerFSM::new(
             vec![
-                ReceiveInput,
+                ReceiveInput { label: None },
                 OffsetVariable {
                     var_index: 0,
                     offset: -1,
@@ -639,40 +1068,449 @@ mod timer_util_tests {
                 },
             ],
             vec![3].into(),
-        );
+        )
+        .unwrap();
+        let now = Duration::ZERO;
         assert_eq!(Idle, model.state);
         assert_eq!(
             PhaseChange {
                 prev_phase: BeginProgram,
-                next_phase: ReceiveInput,
+                next_phase: ReceiveInput { label: None },
                 phase_completed: true
             },
-            model.input(TimerInput::Start)
+            model.input_at(TimerInput::Start, now)
         );
         assert_eq!(
             PhaseChange {
-                prev_phase: ReceiveInput,
-                next_phase: ReceiveInput,
+                prev_phase: ReceiveInput { label: None },
+                next_phase: ReceiveInput { label: None },
                 phase_completed: true
             },
-            model.input(TimerInput::Input)
+            model.input_at(TimerInput::Input, now)
         );
         assert_eq!(
             PhaseChange {
-                prev_phase: ReceiveInput,
-                next_phase: ReceiveInput,
+                prev_phase: ReceiveInput { label: None },
+                next_phase: ReceiveInput { label: None },
                 phase_completed: true
             },
-            model.input(TimerInput::Input)
+            model.input_at(TimerInput::Input, now)
         );
         assert_eq!(
             PhaseChange {
-                prev_phase: ReceiveInput,
+                prev_phase: ReceiveInput { label: None },
                 next_phase: EndProgram,
                 phase_completed: true
             },
-            model.input(TimerInput::Input)
+            model.input_at(TimerInput::Input, now)
         );
         assert_eq!(Idle, model.state);
     }
+
+    #[test]
+    fn invalid_program_rejected() {
+        assert_eq!(
+            vec![Diagnostic::JumpOutOfRange {
+                phase: 1,
+                to_phase: 7
+            }],
+            TimerFSM::new(
+                vec![
+                    ReceiveInput { label: None },
+                    Repeat {
+                        to_phase: 7,
+                        var_index: 0
+                    }
+                ],
+                vec![1].into()
+            )
+            .err()
+            .unwrap()
+            .diagnostics
+        );
+    }
+
+    #[test]
+    fn pause_keeps_fraction_of_second() {
+        let mut model: TimerFSM = TimerFSM::new(
+            vec![TimeFor {
+                duration: 3,
+                label: None,
+            }],
+            None,
+        )
+        .unwrap();
+        model.input_at(Start, Duration::ZERO);
+        assert_eq!(
+            TimerPaused,
+            model.input_at(Pause, Duration::from_millis(1250))
+        );
+        assert_eq!(NoChange, model.input_at(Step, Duration::from_secs(5)));
+        assert_eq!(
+            TimerResumed {
+                remaining: Duration::from_millis(1750)
+            },
+            model.input_at(Resume, Duration::from_secs(10))
+        );
+        assert_eq!(
+            TimerProgress {
+                remaining: Duration::from_millis(1250)
+            },
+            model.input_at(Step, Duration::from_millis(10500))
+        );
+    }
+
+    #[test]
+    fn dropped_steps_do_not_drift() {
+        let mut model: TimerFSM = TimerFSM::new(
+            vec![TimeFor {
+                duration: 3,
+                label: None,
+            }],
+            None,
+        )
+        .unwrap();
+        model.input_at(Start, Duration::ZERO);
+        assert_eq!(
+            TimerProgress {
+                remaining: Duration::from_millis(500)
+            },
+            model.input_at(Step, Duration::from_millis(2500))
+        );
+        assert_eq!(
+            TimerProgress {
+                remaining: Duration::ZERO
+            },
+            model.input_at(Step, Duration::from_millis(3700))
+        );
+        assert_eq!(
+            PhaseChange {
+                prev_phase: TimeFor {
+                    duration: 3,
+                    label: None
+                },
+                next_phase: EndProgram,
+                phase_completed: true
+            },
+            model.input_at(Step, Duration::from_millis(3800))
+        );
+    }
+
+    #[test]
+    fn ignored_input_keeps_elapsed_time() {
+        let mut model: TimerFSM = TimerFSM::new(
+            vec![TimeFor {
+                duration: 3,
+                label: None,
+            }],
+            None,
+        )
+        .unwrap();
+        model.input_at(Start, Duration::ZERO);
+        assert_eq!(
+            NoChange,
+            model.input_at(TimerInput::Input, Duration::from_millis(700))
+        );
+        assert_eq!(
+            TimerProgress {
+                remaining: Duration::from_millis(1900)
+            },
+            model.input_at(Step, Duration::from_millis(1100))
+        );
+    }
+
+    #[test]
+    fn play_sound_program() {
+        let mut model: TimerFSM = TimerFSM::new(
+            vec![
+                ProgramPhase::PlaySound {
+                    sound: Sound::ProgramStart,
+                },
+                TimeFor {
+                    duration: 1,
+                    label: None,
+                },
+                ProgramPhase::PlaySound {
+                    sound: Sound::TimerDone,
+                },
+            ],
+            None,
+        )
+        .unwrap();
+        let mut now = Duration::ZERO;
+        assert_eq!(
+            PhaseChange {
+                prev_phase: BeginProgram,
+                next_phase: TimeFor {
+                    duration: 1,
+                    label: None
+                },
+                phase_completed: true
+            },
+            model.input_at(Start, now)
+        );
+        assert_eq!(
+            vec![TimerOutput::PlaySound {
+                sound: Sound::ProgramStart
+            }],
+            model.take_effects()
+        );
+        assert_eq!(Vec::<TimerOutput>::new(), model.take_effects());
+        assert_eq!(
+            TimerProgress { remaining: secs(0) },
+            step(&mut model, &mut now)
+        );
+        assert_eq!(
+            PhaseChange {
+                prev_phase: TimeFor {
+                    duration: 1,
+                    label: None
+                },
+                next_phase: EndProgram,
+                phase_completed: true
+            },
+            step(&mut model, &mut now)
+        );
+        assert_eq!(
+            vec![TimerOutput::PlaySound {
+                sound: Sound::TimerDone
+            }],
+            model.take_effects()
+        );
+    }
+
+    #[test]
+    fn play_sound_in_loop() {
+        let mut model: TimerFSM = TimerFSM::new(
+            vec![
+                ReceiveInput { label: None },
+                ProgramPhase::PlaySound { sound: Sound::Skip },
+                OffsetVariable {
+                    var_index: 0,
+                    offset: -1,
+                },
+                Repeat {
+                    to_phase: 0,
+                    var_index: 0,
+                },
+            ],
+            vec![2].into(),
+        )
+        .unwrap();
+        let now = Duration::ZERO;
+        model.input_at(Start, now);
+        for _ in 0..2 {
+            model.input_at(TimerInput::Input, now);
+            assert_eq!(
+                vec![TimerOutput::PlaySound { sound: Sound::Skip }],
+                model.take_effects()
+            );
+        }
+        assert_eq!(Idle, model.state);
+    }
+
+    #[test]
+    fn nested_loop_counters_reset() {
+        let (program, variables) = compile(&[ProgramBlock::Loop {
+            count: 2,
+            body: vec![ProgramBlock::Loop {
+                count: 3,
+                body: vec![ReceiveInput { label: None }.into()],
+            }],
+        }])
+        .unwrap();
+        let mut model: TimerFSM = TimerFSM::new(program, Some(variables)).unwrap();
+        let now = Duration::ZERO;
+        model.input_at(Start, now);
+        let mut inputs = 0;
+        while model.state == TimerState::Input {
+            model.input_at(TimerInput::Input, now);
+            inputs += 1;
+        }
+        // The Inner Loop Runs All Three Times on Both Passes of the Outer Loop
+        assert_eq!(6, inputs);
+        assert_eq!(Idle, model.state);
+    }
+
+    #[test]
+    fn long_instant_loops() {
+        let instant = |phase: ProgramPhase| {
+            let (program, variables) = compile(&[
+                ProgramBlock::Loop {
+                    count: i8::MAX,
+                    body: vec![ProgramBlock::Loop {
+                        count: i8::MAX,
+                        body: vec![phase.into()],
+                    }],
+                },
+                ReceiveInput { label: None }.into(),
+            ])
+            .unwrap();
+            TimerFSM::new(program, Some(variables)).unwrap()
+        };
+        // Thousands of Phases That Do Not Wait Pass Straight Through to the Input
+        let mut model = instant(ProgramPhase::DisplayText { text: "x".into() });
+        model.input_at(Start, Duration::ZERO);
+        assert_eq!(TimerState::Input, model.state);
+        assert_eq!(127 * 127, model.take_effects().len());
+        let mut model = instant(ProgramPhase::PlaySound {
+            sound: Sound::Pause,
+        });
+        model.input_at(Start, Duration::ZERO);
+        assert_eq!(TimerState::Input, model.state);
+        assert_eq!(127 * 127, model.take_effects().len());
+    }
+
+    #[test]
+    fn offset_past_the_limit_saturates() {
+        let mut model: TimerFSM = TimerFSM::new(
+            vec![
+                TimeFor {
+                    duration: 1,
+                    label: None,
+                },
+                OffsetVariable {
+                    var_index: 0,
+                    offset: 10,
+                },
+            ],
+            Some(vec![120]),
+        )
+        .unwrap();
+        model.input_at(Start, Duration::ZERO);
+        model.input_at(Skip, Duration::ZERO);
+        assert_eq!(vec![i8::MAX], model.variables);
+        assert_eq!(Idle, model.state);
+    }
+
+    #[test]
+    fn restart_restores_variables() {
+        let mut model: TimerFSM = TimerFSM::new(
+            vec![
+                ReceiveInput { label: None },
+                OffsetVariable {
+                    var_index: 0,
+                    offset: -1,
+                },
+                Repeat {
+                    to_phase: 0,
+                    var_index: 0,
+                },
+            ],
+            Some(vec![2]),
+        )
+        .unwrap();
+        let now = Duration::ZERO;
+        model.input_at(Start, now);
+        model.input_at(TimerInput::Input, now);
+        assert_eq!(vec![1], model.variables);
+        model.input_at(Stop, now);
+        assert_eq!(vec![2], model.variables);
+
+        // Runs Both Passes Again, Then Starts Over Once More After the End
+        model.input_at(Start, now);
+        model.input_at(TimerInput::Input, now);
+        assert_eq!(
+            PhaseChange {
+                prev_phase: ReceiveInput { label: None },
+                next_phase: EndProgram,
+                phase_completed: true
+            },
+            model.input_at(TimerInput::Input, now)
+        );
+        assert_eq!(
+            PhaseChange {
+                prev_phase: BeginProgram,
+                next_phase: ReceiveInput { label: None },
+                phase_completed: true
+            },
+            model.input_at(Start, now)
+        );
+        assert_eq!(vec![2], model.variables);
+    }
+
+    #[test]
+    fn status_follows_state() {
+        let mut model: TimerFSM = TimerFSM::new(
+            vec![
+                ReceiveInput { label: None },
+                TimeFor {
+                    duration: 3,
+                    label: None,
+                },
+            ],
+            None,
+        )
+        .unwrap();
+        let now = Duration::ZERO;
+        assert_eq!(TimerStatus::Idle, model.status());
+        model.input_at(Start, now);
+        assert_eq!(TimerStatus::AwaitingInput, model.status());
+        model.input_at(TimerInput::Input, now);
+        assert_eq!(TimerStatus::Running, model.status());
+        model.input_at(Pause, now);
+        assert_eq!(TimerStatus::Paused, model.status());
+        model.input_at(Resume, now);
+        assert_eq!(TimerStatus::Running, model.status());
+        model.input_at(Stop, now);
+        assert_eq!(TimerStatus::Idle, model.status());
+    }
+
+    #[test]
+    fn snapshot_between_inputs() {
+        let mut model: TimerFSM = TimerFSM::new(
+            vec![
+                TimeFor {
+                    duration: 10,
+                    label: None,
+                },
+                OffsetVariable {
+                    var_index: 0,
+                    offset: -1,
+                },
+                Repeat {
+                    to_phase: 0,
+                    var_index: 0,
+                },
+            ],
+            Some(vec![2]),
+        )
+        .unwrap();
+        assert_eq!(
+            TimerSnapshot {
+                status: TimerStatus::Idle,
+                phase_index: 0,
+                phase: None,
+                remaining: None,
+                elapsed: None,
+                program_remaining: Some(secs(20)),
+                program_total: Some(secs(20)),
+                variables: vec![2],
+                program_len: 3,
+            },
+            model.snapshot_at(Duration::ZERO)
+        );
+        model.input_at(Start, Duration::ZERO);
+        // No Step Has Arrived Yet, but the Snapshot Still Counts the Time
+        let snapshot = model.snapshot_at(Duration::from_millis(2500));
+        assert_eq!(Some(Duration::from_millis(7500)), snapshot.remaining);
+        assert_eq!(Some(Duration::from_millis(2500)), snapshot.elapsed);
+        assert_eq!(
+            Some(Duration::from_millis(17500)),
+            snapshot.program_remaining
+        );
+        assert_eq!(
+            Some(TimeFor {
+                duration: 10,
+                label: None
+            }),
+            snapshot.phase
+        );
+        model.input_at(Pause, Duration::from_secs(3));
+        assert!(model.is_paused());
+        assert_eq!(
+            Some(secs(7)),
+            model.snapshot_at(Duration::from_secs(60)).remaining
+        );
+    }
 }
@@ -1,78 +1,54 @@
+use crate::audio_util::{cues_for, default_backend, AudioBackend, AudioError, Sound};
+use crate::duration_util::{
+    display_seconds, program_time_string, seconds_to_h_m_s_display_string, total_duration,
+};
+use crate::editor_util::ProgramEditor;
+use crate::library_util::ProgramLibrary;
+use crate::program_util::Program;
+use crate::stats_util::{date_string, week_of, Stats};
+use crate::timer_util::TimerSnapshot;
 use crate::AppWindow;
 use crate::ProgramPhase;
 use crate::TimerOutput;
+use crate::TimerStatus;
+use crate::{FocusBar, LibraryProgram, StatsSummary};
 
-use anyhow::Result;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use std::time::Duration;
 
 pub fn data_to_ui(output: TimerOutput, ui_handle: &slint::Weak<AppWindow>) {
     let ui = ui_handle.unwrap();
+    for sound in cues_for(&output) {
+        play_cue(sound, &ui);
+    }
     match output {
-        TimerOutput::NoChange => {}
+        TimerOutput::NoChange | TimerOutput::TimerPaused | TimerOutput::PlaySound { .. } => {}
         TimerOutput::ProgramStopped { program_phase } => {
-            match program_phase {
-                ProgramPhase::TimeFor { duration: _ } => {
-                    ui.invoke_stop_timer();
-                    ui.set_timer_string("Stopped".into());
-                }
-                _ => {}
-            }
-            let _ = play_sound("assets/Program-Stopped-Sound.mp3".into());
-            ui.set_timer_string("Ready to Start".into()); // TODO Probably want a Reset UI function to invoke
-        }
-        TimerOutput::TimerProgress { seconds } => {
-            ui.set_timer_string(seconds_to_h_m_s_display_string(seconds).into());
-            if seconds == 0 {
-                let _ = play_sound("assets/Timer-Done-Sound.mp3".into()); // TODO Error Handling
+            if let ProgramPhase::TimeFor { .. } = program_phase {
+                ui.set_timer_string("Stopped".into());
             }
+            reset_display(&ui);
         }
-        TimerOutput::TimerPaused => {
-            let _ = play_sound("assets/Pause-Sound.mp3".into());
-            ui.set_timer_string(format!("|| {}", ui.get_timer_string().to_string()).into());
-        }
-        TimerOutput::TimerResumed { seconds } => {
-            ui.invoke_start_timer();
-            ui.set_timer_string(seconds_to_h_m_s_display_string(seconds).into());
-            let _ = play_sound("assets/Resume-Sound.mp3".into()); // TODO Error Handling
+        TimerOutput::TimerProgress { remaining }
+        | TimerOutput::TimerResumed { remaining }
+        | TimerOutput::TimerReset { remaining } => {
+            ui.set_timer_string(seconds_to_h_m_s_display_string(display_seconds(remaining)).into());
         }
-        TimerOutput::TimerReset { seconds } => {
-            ui.set_timer_string(seconds_to_h_m_s_display_string(seconds).into());
-            let _ = play_sound("assets/Reset-Sound.mp3".into()); // TODO Error Handling
+        TimerOutput::DisplayText { text } => {
+            ui.set_display_text(text.into());
         }
-        TimerOutput::PhaseChange {
-            prev_phase,
-            next_phase,
-            phase_completed,
-        } => {
-            match prev_phase {
-                ProgramPhase::BeginProgram => {
-                    let _ = play_sound("assets/Program-Start-Sound.mp3".into());
-                    // TODO Error Handling
-                }
-                ProgramPhase::TimeFor { duration: _ } => {
-                    if phase_completed {
-                    } else {
-                        let _ = play_sound("assets/Skip-Sound.mp3".into()); // TODO Error Handling
-                    }
-                }
-                ProgramPhase::EndProgram => {}
-                ProgramPhase::ReceiveInput => {
-                    let _ = play_sound("assets/Timer-Done-Sound.mp3".into());
-                }
-                _ => {}
-            }
+        TimerOutput::PhaseChange { next_phase, .. } => {
+            ui.set_phase_label(next_phase.label().unwrap_or_default().into());
             match next_phase {
-                ProgramPhase::TimeFor { duration } => {
-                    ui.invoke_start_timer();
+                ProgramPhase::TimeFor { duration, .. } => {
                     ui.set_timer_string(seconds_to_h_m_s_display_string(duration).into());
                 }
                 ProgramPhase::EndProgram => {
-                    let _ = play_sound("assets/Program-Done-Sound.mp3".into()); // TODO Error Handling
                     ui.set_timer_string("Ready to Start".into());
+                    ui.set_display_text("".into());
                 }
-                ProgramPhase::BeginProgram => {
-                    let _ = play_sound("assets/Program-Done-Sound.mp3".into());
-                }
-                ProgramPhase::ReceiveInput => {
+                ProgramPhase::ReceiveInput { .. } => {
                     ui.set_timer_string("Input".into());
                 }
                 _ => {}
@@ -81,49 +57,283 @@ pub fn data_to_ui(output: TimerOutput, ui_handle: &slint::Weak<AppWindow>) {
     }
 }
 
-fn seconds_to_h_m_s_display_string(total_seconds: usize) -> String {
-    let hours = total_seconds / 3600;
-    let minutes = (total_seconds - hours * 3600) / 60;
-    let seconds = total_seconds - hours * 3600 - minutes * 60;
-    return format!("{:02}:{:02}:{:02}", hours, minutes, seconds);
+/**
+ * Puts the Timer View Back to How It Looks Before a Program Starts
+ */
+pub fn reset_display(ui: &AppWindow) {
+    ui.set_timer_string("Ready to Start".into());
+    ui.set_phase_label("".into());
+    ui.set_display_text("".into());
 }
 
-pub fn play_sound(path: Option<&str>) -> Result<()> {
-    let audio_path: &str = path.unwrap_or("assets/Timer-Done-Sound.mp3");
-    let result = web_sys::HtmlAudioElement::new_with_src(audio_path);
-    let _ = result.unwrap().play();
+/**
+ * Draws the Parts of the Timer That Depend on the Whole FSM Rather Than One Output
+ */
+pub fn snapshot_to_ui(snapshot: &TimerSnapshot, ui_handle: &slint::Weak<AppWindow>) {
+    let ui = ui_handle.unwrap();
+    ui.set_timer_status(snapshot.status);
+    ui.set_program_time(program_time_string(snapshot).into());
+    ui.set_phase_progress(phase_progress(snapshot));
+    ui.set_program_progress(program_progress(snapshot));
+}
 
-    return Ok(());
+/**
+ * How Far Through the Current Timer, From 0 to 1; 0 When No Timer Is Active
+ */
+fn phase_progress(snapshot: &TimerSnapshot) -> f32 {
+    match (snapshot.elapsed, snapshot.remaining) {
+        (Some(elapsed), Some(remaining)) if !(elapsed + remaining).is_zero() => {
+            elapsed.as_secs_f32() / (elapsed + remaining).as_secs_f32()
+        }
+        _ => 0.0,
+    }
+}
+
+/**
+ * How Much of the Program's Timed Length Has Passed, From 0 to 1
+ */
+fn program_progress(snapshot: &TimerSnapshot) -> f32 {
+    if snapshot.status == TimerStatus::Idle {
+        return 0.0;
+    }
+    match (snapshot.program_total, snapshot.program_remaining) {
+        (Some(total), Some(remaining)) if !total.is_zero() => {
+            1.0 - remaining.min(total).as_secs_f32() / total.as_secs_f32()
+        }
+        _ => 0.0,
+    }
+}
+
+pub fn editor_to_ui(editor: &ProgramEditor, ui_handle: &slint::Weak<AppWindow>) {
+    let ui = ui_handle.unwrap();
+    let rows = slint::VecModel::from(editor.rows());
+    ui.set_editor_phases(slint::ModelRc::new(rows));
+    ui.set_editor_name(editor.program().name.clone().into());
+    ui.set_editor_status(editor.status().into());
+}
+
+/**
+ * Lists the Library, Marking the Program Named `selected` as the One Loaded in the Timer
+ */
+pub fn library_to_ui(library: &ProgramLibrary, selected: &str, ui_handle: &slint::Weak<AppWindow>) {
+    let programs: Vec<LibraryProgram> = library
+        .programs()
+        .map(|program| LibraryProgram {
+            name: program.name.clone().into(),
+            description: program.description.clone().into(),
+            length: program_length(program).into(),
+            built_in: library.is_built_in(&program.name),
+            selected: program.name == selected,
+        })
+        .collect();
+    ui_handle
+        .unwrap()
+        .set_library_programs(slint::ModelRc::new(slint::VecModel::from(programs)));
+}
+
+/**
+ * Timed Length of a Whole Run, Leaving Out Waits for Input
+ */
+fn program_length(program: &Program) -> String {
+    match total_duration(&program.phases, &program.variables) {
+        Some(total) => seconds_to_h_m_s_display_string(display_seconds(total)),
+        None => "too long to estimate".to_string(),
+    }
+}
+
+/**
+ * Fills the Stats View With the Last Week of Days and the Last Four Weeks Up to `today`
+ */
+pub fn stats_to_ui(stats: &Stats, today: i64, ui_handle: &slint::Weak<AppWindow>) {
+    let ui = ui_handle.unwrap();
+    ui.set_stats_summary(StatsSummary {
+        runs: stats.runs as i32,
+        finished_runs: stats.finished_runs as i32,
+        completed: stats.completed_phases as i32,
+        skipped: stats.skipped_phases as i32,
+        stopped: stats.stopped_phases as i32,
+        average_pauses: format!("{:.1}", stats.average_pauses).into(),
+        break_minutes: (stats.break_time.as_secs() / 60) as i32,
+        current_streak: stats.current_streak as i32,
+        longest_streak: stats.longest_streak as i32,
+    });
+    let days = focus_bars(&stats.focus_by_day, today - 6..=today, date_string);
+    let this_week = week_of(today);
+    // Week Zero Starts on Monday 1969-12-29, Three Days Before the Epoch
+    let weeks = focus_bars(&stats.focus_by_week, this_week - 3..=this_week, |week| {
+        format!("Week of {}", date_string(week * 7 - 3))
+    });
+    ui.set_stats_days(slint::ModelRc::new(slint::VecModel::from(days)));
+    ui.set_stats_weeks(slint::ModelRc::new(slint::VecModel::from(weeks)));
+}
+
+/**
+ * One Bar per Key, Including Empty Ones, Scaled so the Longest Fills the Row
+ */
+fn focus_bars(
+    focus: &BTreeMap<i64, Duration>,
+    keys: impl Iterator<Item = i64> + Clone,
+    label: impl Fn(i64) -> String,
+) -> Vec<FocusBar> {
+    let longest = keys
+        .clone()
+        .filter_map(|key| focus.get(&key))
+        .max()
+        .copied()
+        .unwrap_or_default();
+    keys.map(|key| {
+        let time = focus.get(&key).copied().unwrap_or_default();
+        FocusBar {
+            label: label(key).into(),
+            minutes: (time.as_secs() / 60) as i32,
+            fraction: if longest.is_zero() {
+                0.0
+            } else {
+                time.as_secs_f32() / longest.as_secs_f32()
+            },
+        }
+    })
+    .collect()
+}
+
+thread_local! {
+    // Sounds Are Only Played From the UI Thread, Which Owns the Backend
+    static AUDIO: Box<dyn AudioBackend> = default_backend();
+}
+
+/**
+ * Sends Audio Errors That Only Show Up Later, Like a Blocked Autoplay, to the UI
+ */
+pub fn init_audio(ui_handle: &slint::Weak<AppWindow>) {
+    let ui_handle = ui_handle.clone();
+    AUDIO.with(|audio| {
+        audio.set_error_handler(Rc::new(move |error| {
+            if let Some(ui) = ui_handle.upgrade() {
+                show_audio_error(error, &ui);
+            }
+        }))
+    });
+}
+
+pub fn play_sound(sound: Sound) -> Result<(), AudioError> {
+    AUDIO.with(|audio| audio.play(sound))
+}
+
+/**
+ * Plays a Sound, Showing the Audio Banner if It Fails
+ */
+pub fn play_cue(sound: Sound, ui: &AppWindow) {
+    if let Err(error) = play_sound(sound) {
+        show_audio_error(error, ui);
+    }
+}
+
+pub fn show_audio_error(error: AudioError, ui: &AppWindow) {
+    log_error(&error.to_string());
+    ui.set_audio_error(audio_error_banner(&error).into());
+}
+
+fn audio_error_banner(error: &AudioError) -> String {
+    match error {
+        AudioError::Blocked => "Sound blocked - click to enable".into(),
+        _ => format!("No sound ({}) - click to retry", error),
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn log_error(message: &str) {
+    web_sys::console::error_1(&message.into());
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn log_error(message: &str) {
+    eprintln!("{}", message);
 }
 
 #[cfg(test)]
 mod ui_util_tests {
 
-    use super::seconds_to_h_m_s_display_string;
+    use super::{audio_error_banner, focus_bars, phase_progress, program_length, program_progress};
+    use crate::audio_util::AudioError;
+    use crate::block_util::{compile, ProgramBlock};
+    use crate::program_util::Program;
+    use crate::timer_util::TimerSnapshot;
+    use crate::TimerStatus;
+    use std::time::Duration;
     // use std::panic;
 
     #[test]
-    fn just_seconds() {
-        assert_eq!("00:00:15", seconds_to_h_m_s_display_string(15));
-    }
-
-    #[test]
-    fn just_minutes() {
-        assert_eq!("00:15:00", seconds_to_h_m_s_display_string(15 * 60));
+    fn audio_error_banners() {
+        assert_eq!(
+            "Sound blocked - click to enable",
+            audio_error_banner(&AudioError::Blocked)
+        );
+        assert_eq!(
+            "No sound (no audio output device: NoDevice) - click to retry",
+            audio_error_banner(&AudioError::NoOutputDevice("NoDevice".into()))
+        );
     }
 
     #[test]
-    fn just_hours() {
-        assert_eq!("15:00:00", seconds_to_h_m_s_display_string(15 * 3600));
+    fn library_lengths() {
+        let timed = Program::new(
+            "Timed",
+            vec![
+                crate::ProgramPhase::TimeFor {
+                    duration: 90,
+                    label: None,
+                },
+                crate::ProgramPhase::ReceiveInput { label: None },
+            ],
+            vec![],
+        );
+        assert_eq!("00:01:30", program_length(&timed));
+        let second = crate::ProgramPhase::TimeFor {
+            duration: 1,
+            label: None,
+        };
+        let nested = |body: ProgramBlock| ProgramBlock::Loop {
+            count: i8::MAX,
+            body: vec![body],
+        };
+        let (phases, variables) = compile(&[nested(nested(nested(second.into())))]).unwrap();
+        let huge = Program::new("Huge", phases, variables);
+        assert_eq!("too long to estimate", program_length(&huge));
     }
 
     #[test]
-    fn minute_threshold() {
-        assert_eq!("00:01:00", seconds_to_h_m_s_display_string(60));
+    fn progress_fractions() {
+        let mut snapshot = TimerSnapshot {
+            status: TimerStatus::Running,
+            phase_index: 0,
+            phase: None,
+            remaining: Some(Duration::from_secs(30)),
+            elapsed: Some(Duration::from_secs(10)),
+            program_remaining: Some(Duration::from_secs(90)),
+            program_total: Some(Duration::from_secs(120)),
+            variables: vec![],
+            program_len: 1,
+        };
+        assert_eq!(0.25, phase_progress(&snapshot));
+        assert_eq!(0.25, program_progress(&snapshot));
+        snapshot.status = TimerStatus::Idle;
+        snapshot.remaining = None;
+        assert_eq!(0.0, phase_progress(&snapshot));
+        assert_eq!(0.0, program_progress(&snapshot));
     }
 
     #[test]
-    fn hour_threshold() {
-        assert_eq!("01:00:00", seconds_to_h_m_s_display_string(3600));
+    fn focus_bars_scale_to_longest() {
+        let focus = std::collections::BTreeMap::from([
+            (1, Duration::from_secs(600)),
+            (3, Duration::from_secs(1200)),
+        ]);
+        let bars = focus_bars(&focus, 1..=4, |key| key.to_string());
+        assert_eq!(4, bars.len());
+        assert_eq!("1", bars[0].label.as_str());
+        assert_eq!(10, bars[0].minutes);
+        assert_eq!(0.5, bars[0].fraction);
+        assert_eq!(0.0, bars[1].fraction);
+        assert_eq!(1.0, bars[2].fraction);
     }
 }
@@ -0,0 +1,486 @@
+use crate::ProgramPhase;
+
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Diagnostic {
+    EmptyProgram,
+    JumpOutOfRange { phase: usize, to_phase: usize },
+    ForwardJump { phase: usize, to_phase: usize },
+    MissingVariable { phase: usize, var_index: usize },
+    NonTerminatingLoop { phase: usize, to_phase: usize },
+    CounterSetInLoop { phase: usize, set_phase: usize },
+    OverflowRisk { phase: usize, var_index: usize },
+}
+
+impl Diagnostic {
+    /**
+     * Errors Would Panic or Hang the FSM; Warnings Describe Programs That Run but Misbehave
+     */
+    pub fn severity(&self) -> Severity {
+        match self {
+            Diagnostic::OverflowRisk { .. } => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Diagnostic::EmptyProgram => write!(f, "program has no phases"),
+            Diagnostic::JumpOutOfRange { phase, to_phase } => write!(
+                f,
+                "phase {}: repeat jumps to phase {}, which does not exist",
+                phase, to_phase
+            ),
+            Diagnostic::ForwardJump { phase, to_phase } => write!(
+                f,
+                "phase {}: repeat jumps forward to phase {}, repeats can only jump back",
+                phase, to_phase
+            ),
+            Diagnostic::MissingVariable { phase, var_index } => {
+                write!(f, "phase {}: variable {} does not exist", phase, var_index)
+            }
+            Diagnostic::NonTerminatingLoop { phase, to_phase } => write!(
+                f,
+                "phase {}: loop back to phase {} never ends, its counter never reaches zero",
+                phase, to_phase
+            ),
+            Diagnostic::CounterSetInLoop { phase, set_phase } => write!(
+                f,
+                "phase {}: loop sets its own counter at phase {}, so it starts over every pass",
+                phase, set_phase
+            ),
+            Diagnostic::OverflowRisk { phase, var_index } => write!(
+                f,
+                "phase {}: variable {} can run past zero and overflow",
+                phase, var_index
+            ),
+        }
+    }
+}
+
+/**
+ * Returned by TimerFSM::new When the Program Has Error Diagnostics
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationError {
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let messages: Vec<String> = self
+            .diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.to_string())
+            .collect();
+        write!(f, "invalid program: {}", messages.join("; "))
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/**
+ * Whether a Counter Starting at `initial` and Moved by `step` per Pass Lands Exactly on Zero
+ */
+fn reaches_zero(initial: i8, step: i32) -> bool {
+    let initial = initial as i32;
+    step != 0 && -initial % step == 0 && -initial / step >= 1
+}
+
+/**
+ * Checks a Program Before It Is Run, Using the Initial Variables to Predict Loop Counters
+ */
+pub fn validate(program: &[ProgramPhase], variables: &[i8]) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = vec![];
+    if program.is_empty() {
+        diagnostics.push(Diagnostic::EmptyProgram);
+    }
+
+    let mut looped_offsets: Vec<usize> = vec![];
+    for (phase, program_phase) in program.iter().enumerate() {
+        match *program_phase {
+            ProgramPhase::Repeat {
+                to_phase,
+                var_index,
+            } => {
+                let mut valid = true;
+                if to_phase >= program.len() {
+                    diagnostics.push(Diagnostic::JumpOutOfRange { phase, to_phase });
+                    valid = false;
+                } else if to_phase > phase {
+                    // Only Backward Loops Are Checked for Ending, so a Forward Jump Could Hang
+                    diagnostics.push(Diagnostic::ForwardJump { phase, to_phase });
+                    valid = false;
+                }
+                if var_index >= variables.len() {
+                    diagnostics.push(Diagnostic::MissingVariable { phase, var_index });
+                    valid = false;
+                }
+                if !valid {
+                    continue;
+                }
+
+                let body = &program[to_phase..=phase];
+                let mut step: i32 = 0;
+                let mut resets_counter = false;
+                for (index, body_phase) in (to_phase..=phase).zip(body) {
+                    match *body_phase {
+                        ProgramPhase::OffsetVariable {
+                            var_index: offset_index,
+                            offset,
+                        } if offset_index == var_index => {
+                            step += offset as i32;
+                            looped_offsets.push(index);
+                        }
+                        ProgramPhase::SetVariable {
+                            var_index: set_index,
+                            ..
+                        } if set_index == var_index => {
+                            diagnostics.push(Diagnostic::CounterSetInLoop {
+                                phase,
+                                set_phase: index,
+                            });
+                            resets_counter = true;
+                        }
+                        _ => {}
+                    }
+                }
+                if resets_counter {
+                    continue;
+                }
+                // Loops Built From Blocks Reset Their Counter Right Before the Body
+                let initial = match to_phase.checked_sub(1).map(|index| &program[index]) {
+                    Some(&ProgramPhase::SetVariable {
+                        var_index: set_index,
+                        value,
+                    }) if set_index == var_index => value,
+                    _ => variables[var_index],
+                };
+                // Waiting in the Body Only Slows Down a Loop Whose Counter Never Hits Zero
+                let ends = reaches_zero(initial, step) || (step == 0 && initial == 0);
+                if !ends {
+                    diagnostics.push(Diagnostic::NonTerminatingLoop { phase, to_phase });
+                }
+            }
+            ProgramPhase::OffsetVariable { var_index, .. }
+            | ProgramPhase::SetVariable { var_index, .. }
+                if var_index >= variables.len() =>
+            {
+                diagnostics.push(Diagnostic::MissingVariable { phase, var_index });
+            }
+            _ => {}
+        }
+    }
+
+    // Offsets Outside of Their Own Loop Run Once, so the Initial Value Tells if They Overflow
+    for (phase, program_phase) in program.iter().enumerate() {
+        if let ProgramPhase::OffsetVariable { var_index, offset } = *program_phase {
+            if var_index < variables.len()
+                && !looped_offsets.contains(&phase)
+                && variables[var_index].checked_add(offset).is_none()
+            {
+                diagnostics.push(Diagnostic::OverflowRisk { phase, var_index });
+            }
+        }
+    }
+    diagnostics
+}
+
+/**
+ * Fails With Every Error Diagnostic, Ignoring Warnings
+ */
+pub fn check(program: &[ProgramPhase], variables: &[i8]) -> Result<(), ValidationError> {
+    let errors: Vec<Diagnostic> = validate(program, variables)
+        .into_iter()
+        .filter(|diagnostic| diagnostic.severity() == Severity::Error)
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ValidationError {
+            diagnostics: errors,
+        })
+    }
+}
+
+#[cfg(test)]
+mod validator_util_tests {
+
+    use super::{check, validate, Diagnostic, Severity};
+    use crate::ProgramPhase::*;
+
+    #[test]
+    fn valid_loop() {
+        let program = vec![
+            ReceiveInput { label: None },
+            OffsetVariable {
+                var_index: 0,
+                offset: -1,
+            },
+            Repeat {
+                to_phase: 0,
+                var_index: 0,
+            },
+        ];
+        assert_eq!(Vec::<Diagnostic>::new(), validate(&program, &[3]));
+        assert!(check(&program, &[3]).is_ok());
+    }
+
+    #[test]
+    fn empty_program() {
+        assert_eq!(vec![Diagnostic::EmptyProgram], validate(&[], &[]));
+    }
+
+    #[test]
+    fn jump_out_of_range() {
+        assert_eq!(
+            vec![Diagnostic::JumpOutOfRange {
+                phase: 1,
+                to_phase: 5
+            }],
+            validate(
+                &[
+                    ReceiveInput { label: None },
+                    Repeat {
+                        to_phase: 5,
+                        var_index: 0
+                    }
+                ],
+                &[1]
+            )
+        );
+    }
+
+    #[test]
+    fn forward_jump() {
+        // Jumps Over the Countdown Into a Loop That Then Spins Without Ever Waiting
+        let program = vec![
+            Repeat {
+                to_phase: 2,
+                var_index: 1,
+            },
+            OffsetVariable {
+                var_index: 0,
+                offset: -1,
+            },
+            Repeat {
+                to_phase: 0,
+                var_index: 0,
+            },
+        ];
+        assert_eq!(
+            vec![Diagnostic::ForwardJump {
+                phase: 0,
+                to_phase: 2
+            }],
+            validate(&program, &[3, 1])
+        );
+        assert!(check(&program, &[3, 1]).is_err());
+    }
+
+    #[test]
+    fn counter_set_in_loop() {
+        // The Reset Belongs Right Before the Body, Inside It the Counter Never Gets Past Two
+        let program = vec![
+            SetVariable {
+                var_index: 0,
+                value: 3,
+            },
+            OffsetVariable {
+                var_index: 0,
+                offset: -1,
+            },
+            Repeat {
+                to_phase: 0,
+                var_index: 0,
+            },
+        ];
+        assert_eq!(
+            vec![Diagnostic::CounterSetInLoop {
+                phase: 2,
+                set_phase: 0
+            }],
+            validate(&program, &[1])
+        );
+        assert!(check(&program, &[1]).is_err());
+    }
+
+    #[test]
+    fn missing_variable() {
+        assert_eq!(
+            vec![
+                Diagnostic::MissingVariable {
+                    phase: 1,
+                    var_index: 1
+                },
+                Diagnostic::MissingVariable {
+                    phase: 2,
+                    var_index: 1
+                },
+            ],
+            validate(
+                &[
+                    ReceiveInput { label: None },
+                    OffsetVariable {
+                        var_index: 1,
+                        offset: -1
+                    },
+                    Repeat {
+                        to_phase: 0,
+                        var_index: 1
+                    }
+                ],
+                &[1]
+            )
+        );
+    }
+
+    #[test]
+    fn zero_duration_loop() {
+        let program = vec![
+            ReceiveInput { label: None },
+            OffsetVariable {
+                var_index: 0,
+                offset: 1,
+            },
+            Repeat {
+                to_phase: 1,
+                var_index: 0,
+            },
+        ];
+        assert_eq!(
+            vec![Diagnostic::NonTerminatingLoop {
+                phase: 2,
+                to_phase: 1
+            }],
+            validate(&program, &[2])
+        );
+        assert!(check(&program, &[2]).is_err());
+        // Counting Down Still Ends Without Waiting
+        assert!(check(&program, &[-2]).is_ok());
+    }
+
+    #[test]
+    fn loop_without_offset() {
+        assert_eq!(
+            vec![Diagnostic::NonTerminatingLoop {
+                phase: 0,
+                to_phase: 0
+            }],
+            validate(
+                &[Repeat {
+                    to_phase: 0,
+                    var_index: 0
+                }],
+                &[1]
+            )
+        );
+    }
+
+    #[test]
+    fn overflow_risk() {
+        let program = vec![
+            TimeFor {
+                duration: 1,
+                label: None,
+            },
+            OffsetVariable {
+                var_index: 0,
+                offset: 10,
+            },
+        ];
+        let diagnostics = validate(&program, &[120]);
+        assert_eq!(
+            vec![Diagnostic::OverflowRisk {
+                phase: 1,
+                var_index: 0
+            }],
+            diagnostics
+        );
+        assert_eq!(Severity::Warning, diagnostics[0].severity());
+        assert!(check(&program, &[120]).is_ok());
+    }
+
+    #[test]
+    fn waiting_loop_that_never_ends() {
+        let program = |offset: i8| {
+            vec![
+                TimeFor {
+                    duration: 1,
+                    label: None,
+                },
+                OffsetVariable {
+                    var_index: 0,
+                    offset,
+                },
+                Repeat {
+                    to_phase: 0,
+                    var_index: 0,
+                },
+            ]
+        };
+        let never_ends = vec![Diagnostic::NonTerminatingLoop {
+            phase: 2,
+            to_phase: 0,
+        }];
+        // Counting Away From Zero, Stepping Over It, or Starting Below It
+        assert_eq!(never_ends, validate(&program(1), &[3]));
+        assert_eq!(never_ends, validate(&program(-2), &[3]));
+        assert_eq!(never_ends, validate(&program(-1), &[0]));
+        assert!(check(&program(-1), &[0]).is_err());
+        assert!(check(&program(-1), &[3]).is_ok());
+    }
+
+    #[test]
+    fn error_message() {
+        assert_eq!(
+            "invalid program: phase 0: variable 2 does not exist",
+            check(
+                &[OffsetVariable {
+                    var_index: 2,
+                    offset: -1
+                }],
+                &[]
+            )
+            .unwrap_err()
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn loop_reset_counts_as_initial() {
+        let program = vec![
+            SetVariable {
+                var_index: 0,
+                value: 3,
+            },
+            ReceiveInput { label: None },
+            OffsetVariable {
+                var_index: 0,
+                offset: -1,
+            },
+            Repeat {
+                to_phase: 1,
+                var_index: 0,
+            },
+        ];
+        // The Counter Starts at Zero but Is Set to Three Before the Loop Is Entered
+        assert_eq!(Vec::<Diagnostic>::new(), validate(&program, &[0]));
+        assert_eq!(
+            vec![Diagnostic::MissingVariable {
+                phase: 0,
+                var_index: 0
+            }],
+            validate(&program[..1], &[])
+        );
+    }
+}